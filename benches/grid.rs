@@ -0,0 +1,94 @@
+// SparseGrid2dのinsert,queryをTILE_SIZE毎に測る
+// cargo bench --bench grid
+use bevy::prelude::*;
+use bevyruman::sparse_grid::{Aabb, SparseGrid2d};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::hint::black_box;
+
+const COUNTS: [usize; 2] = [1000, 5000];
+const RADIUS: f32 = 4.;
+
+// 敵と同じくらいの密度,1個あたり8x8くらい
+fn bodies(count: usize) -> Vec<(Entity, Aabb)> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let half = (count as f32).sqrt() * 4.;
+    (0..count)
+        .map(|i| {
+            let pos = Vec2::new(rng.gen_range(-half..half), rng.gen_range(-half..half));
+            (Entity::from_raw(i as u32), Aabb::from_circle(pos, RADIUS))
+        })
+        .collect()
+}
+
+fn filled<const TILE_SIZE: usize>(bodies: &[(Entity, Aabb)]) -> SparseGrid2d<TILE_SIZE> {
+    let mut grid = SparseGrid2d::<TILE_SIZE>::default();
+    for (entity, aabb) in bodies {
+        grid.insert_aabb(*aabb, *entity);
+    }
+    grid
+}
+
+fn bench_tile<const TILE_SIZE: usize>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(format!("sparse_grid_tile{TILE_SIZE}"));
+    for count in COUNTS {
+        let bodies = bodies(count);
+        // soft_clearしてから全部入れ直す,毎tickの再構築
+        let mut grid = filled::<TILE_SIZE>(&bodies);
+        group.bench_function(BenchmarkId::new("insert", count), |b| {
+            b.iter(|| {
+                grid.soft_clear();
+                for (entity, aabb) in &bodies {
+                    grid.insert_aabb(*aabb, *entity);
+                }
+            })
+        });
+        // 少し動かして戻す,cellが変わったものだけ更新
+        let moved: Vec<_> = bodies
+            .iter()
+            .map(|(e, aabb)| {
+                let d = Vec2::splat(3.);
+                (
+                    *e,
+                    *aabb,
+                    Aabb {
+                        min: aabb.min + d,
+                        max: aabb.max + d,
+                    },
+                )
+            })
+            .collect();
+        let mut grid = filled::<TILE_SIZE>(&bodies);
+        group.bench_function(BenchmarkId::new("update", count), |b| {
+            b.iter(|| {
+                for (entity, from, to) in &moved {
+                    grid.update(*entity, *from, *to);
+                }
+                for (entity, from, to) in &moved {
+                    grid.update(*entity, *to, *from);
+                }
+            })
+        });
+        let grid = filled::<TILE_SIZE>(&bodies);
+        let mut out = Vec::new();
+        group.bench_function(BenchmarkId::new("query_aabb", count), |b| {
+            b.iter(|| {
+                for (_, aabb) in &bodies {
+                    grid.query_aabb_into(*aabb, &mut out);
+                    black_box(&out);
+                }
+            })
+        });
+        let mut pairs = Vec::new();
+        group.bench_function(BenchmarkId::new("pairs", count), |b| {
+            b.iter(|| {
+                grid.pairs_into(&mut pairs);
+                black_box(&pairs);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tile::<4>, bench_tile::<10>, bench_tile::<32>);
+criterion_main!(benches);
@@ -0,0 +1,180 @@
+// physicsの各systemを別々に測る
+// cargo bench --bench physics
+use bevy::{
+    ecs::{
+        schedule::{ExecutorKind, ScheduleLabel},
+        system::RunSystemOnce,
+    },
+    prelude::*,
+    tasks::{ComputeTaskPool, TaskPool},
+};
+use bevyruman::{
+    broadphase::BroadphaseKind,
+    combat::bullet_vs_enemy_system,
+    components::*,
+    physics::{
+        physical_obj_do_verlet_system, physical_obj_pre_proc_system, shm_pre_proc_system,
+        PhysicsResource, SHM,
+    },
+    physics_config::PhysicsConfig,
+    shape::Shape,
+    solver::solve_contacts_system,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+const COUNTS: [usize; 3] = [100, 1000, 5000];
+const DT: f32 = 1. / 60.;
+
+// 測る前に毎回回す,時間には入れない
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Prepare;
+
+// 測るsystem
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Measured;
+
+// 敵n個と,その1/10の弾
+fn physics_app(count: usize, parallel: bool) -> App {
+    let mut app = App::new();
+    let mut time = Time::<()>::default();
+    time.advance_by(Duration::from_secs_f32(DT));
+    app.insert_resource(time)
+        .insert_resource(SHM::new(BroadphaseKind::default()))
+        .insert_resource(PhysicsResource {
+            parallel_collision: parallel,
+            ..default()
+        })
+        .init_resource::<PhysicsConfig>();
+    let mut rng = StdRng::seed_from_u64(0);
+    let half = (count as f32).sqrt() * 4.; //1個あたり8x8くらい
+    let mut random_pos = || Vec2::new(rng.gen_range(-half..half), rng.gen_range(-half..half));
+    for _ in 0..count {
+        let pos = random_pos();
+        app.world.spawn((
+            Transform::from_translation(pos.extend(0.)),
+            CollideCircle::new(CollisionLayer::Enemy),
+            PhysicalObj {
+                old_pos: pos,
+                ..default()
+            },
+            Enemy,
+            Health::from_max(1.),
+        ));
+    }
+    for _ in 0..count / 10 {
+        let pos = random_pos();
+        app.world.spawn((
+            Transform::from_translation(pos.extend(0.)),
+            HitCircle {
+                radius: 0.,
+                shape: Shape::OrientedBox {
+                    half_width: 4.,
+                    half_height: 2.,
+                },
+                ..HitCircle::new(CollisionLayer::PlayerShot)
+            },
+            PhysicalObj {
+                old_pos: pos - Vec2::new(4., 0.), //1tickで4動いた
+                ..default()
+            },
+            DamageSource::default(),
+            FromPlayer,
+        ));
+    }
+    // gridを埋めておく
+    app.world.run_system_once(shm_pre_proc_system);
+    app
+}
+
+// Prepareは時間に入れず,Measuredだけ測る
+fn bench_schedule(c: &mut Criterion, name: &str, parallel: bool, build: impl Fn(&mut App)) {
+    ComputeTaskPool::get_or_init(TaskPool::default);
+    let mut group = c.benchmark_group(name);
+    for count in COUNTS {
+        let mut app = physics_app(count, parallel);
+        app.init_schedule(Prepare).init_schedule(Measured);
+        app.edit_schedule(Measured, |s| {
+            s.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+        build(&mut app);
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    app.world.run_schedule(Prepare);
+                    let start = Instant::now();
+                    app.world.run_schedule(Measured);
+                    total += start.elapsed();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+// 全部少し動かす,gridの更新が起きるように
+fn jitter_system(mut flip: Local<bool>, mut query: Query<&mut Transform, With<CollideCircle>>) {
+    *flip = !*flip;
+    let d = if *flip { 3. } else { -3. };
+    for mut tf in query.iter_mut() {
+        tf.translation.x += d;
+    }
+}
+
+// 弾のdamage,敵のhpを戻す
+fn refill_system(
+    mut bullets: Query<&mut DamageSource>,
+    mut enemies: Query<&mut Health, With<Enemy>>,
+) {
+    for mut dmg in bullets.iter_mut() {
+        dmg.damage = 1.;
+    }
+    for mut health in enemies.iter_mut() {
+        health.hp = 1.;
+    }
+}
+
+fn shm_pre_proc(c: &mut Criterion) {
+    bench_schedule(c, "shm_pre_proc", true, |app| {
+        app.add_systems(Prepare, jitter_system)
+            .add_systems(Measured, shm_pre_proc_system);
+    });
+}
+
+fn solve_contacts(c: &mut Criterion) {
+    for (name, parallel) in [
+        ("solve_contacts_serial", false),
+        ("solve_contacts_parallel", true),
+    ] {
+        bench_schedule(c, name, parallel, |app| {
+            app.add_systems(Prepare, physical_obj_pre_proc_system)
+                .add_systems(Measured, solve_contacts_system);
+        });
+    }
+}
+
+fn bullet_vs_enemy(c: &mut Criterion) {
+    bench_schedule(c, "bullet_vs_enemy", true, |app| {
+        app.add_systems(Prepare, refill_system)
+            .add_systems(Measured, bullet_vs_enemy_system);
+    });
+}
+
+fn verlet(c: &mut Criterion) {
+    bench_schedule(c, "verlet", true, |app| {
+        app.add_systems(Prepare, physical_obj_pre_proc_system)
+            .add_systems(Measured, physical_obj_do_verlet_system);
+    });
+}
+
+criterion_group!(
+    benches,
+    shm_pre_proc,
+    solve_contacts,
+    bullet_vs_enemy,
+    verlet
+);
+criterion_main!(benches);
@@ -0,0 +1,266 @@
+use crate::{
+    components::*,
+    inputmng::{AimInput, InputMngBtn},
+    physics::SHM,
+    pool::Inactive,
+    replay,
+    sparse_grid::Aabb,
+    AppState,
+};
+use bevy::prelude::*;
+
+const ATTRACT_IDLE_SECONDS: f32 = 10.; //titleで放置したらdemo開始
+const AIM_RANGE: f32 = 150.; //弾が届く距離
+const AIM_SEARCH_RADIUS: f32 = AIM_RANGE * 4.; //これより遠い敵は狙わない
+
+// 狙う敵の選び方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BotTarget {
+    #[default]
+    Nearest,
+    Weakest, //射程内でhpが一番少ない
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct BotParams {
+    pub probe_distance: f32, //密度を調べる位置,playerからの距離
+    pub probe_radius: f32,
+    pub seek_radius: f32, //pickupを拾いに行く距離
+    pub danger_weight: f32,
+    pub pickup_weight: f32,
+    pub keep_weight: f32, //同じ方向に進み続ける,ふらつき防止
+    pub target: BotTarget,
+}
+impl Default for BotParams {
+    fn default() -> Self {
+        Self {
+            probe_distance: 24.,
+            probe_radius: 16.,
+            seek_radius: 120.,
+            danger_weight: 1.,
+            pickup_weight: 2.,
+            keep_weight: 0.5,
+            target: BotTarget::Nearest,
+        }
+    }
+}
+
+// botが入力するか,attractはtitleのdemo中
+#[derive(Resource, Debug, Default)]
+pub struct BotControl {
+    pub active: bool,
+    attract: bool,
+}
+impl BotControl {
+    pub fn enabled() -> Self {
+        Self {
+            active: true,
+            attract: false,
+        }
+    }
+}
+
+pub fn is_bot_active(control: Res<BotControl>) -> bool {
+    control.active
+}
+
+fn is_attract(control: Res<BotControl>) -> bool {
+    control.attract
+}
+
+pub struct BotPlugin;
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BotParams>()
+            .init_resource::<BotControl>()
+            .add_systems(
+                FixedPreUpdate,
+                (bot_move_system, bot_aim_system)
+                    .run_if(is_bot_active)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// titleのdemo,入力があれば終わる
+pub struct AttractModePlugin;
+impl Plugin for AttractModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            attract_idle_system.run_if(in_state(AppState::Title)),
+        )
+        .add_systems(
+            Update,
+            (replay::auto_advance_menu_system, attract_exit_system)
+                .chain()
+                .run_if(is_attract),
+        );
+    }
+}
+
+// 8方向,それぞれ押すボタン
+const D: f32 = std::f32::consts::FRAC_1_SQRT_2;
+const DIRS: [(Vec2, &[InputMngBtn]); 8] = [
+    (Vec2::new(0., 1.), &[InputMngBtn::Up]),
+    (Vec2::new(D, D), &[InputMngBtn::Up, InputMngBtn::Right]),
+    (Vec2::new(1., 0.), &[InputMngBtn::Right]),
+    (Vec2::new(D, -D), &[InputMngBtn::Down, InputMngBtn::Right]),
+    (Vec2::new(0., -1.), &[InputMngBtn::Down]),
+    (Vec2::new(-D, -D), &[InputMngBtn::Down, InputMngBtn::Left]),
+    (Vec2::new(-1., 0.), &[InputMngBtn::Left]),
+    (Vec2::new(-D, D), &[InputMngBtn::Up, InputMngBtn::Left]),
+];
+const MOVE_BTNS: [InputMngBtn; 4] = [
+    InputMngBtn::Up,
+    InputMngBtn::Down,
+    InputMngBtn::Left,
+    InputMngBtn::Right,
+];
+
+// 敵の密集を避けて,pickupを拾う
+fn bot_move_system(
+    params: Res<BotParams>,
+    shm: Res<SHM>,
+    mut scratch: Local<Vec<Entity>>,
+    mut input: ResMut<ButtonInput<InputMngBtn>>,
+    pl_query: Query<&Transform, With<Player>>,
+    ene_query: Query<(), (With<Enemy>, Without<Inactive>)>,
+    pickup_query: Query<&Transform, (With<Pickup>, Without<Inactive>)>,
+) {
+    let Ok(pl_tf) = pl_query.get_single() else {
+        return;
+    };
+    let pos = pl_tf.translation.xy();
+    let mut density = |p: Vec2| {
+        shm.sg2
+            .query_aabb_into(Aabb::from_circle(p, params.probe_radius), &mut scratch);
+        scratch.iter().filter(|e| ene_query.contains(**e)).count() as f32
+    };
+
+    // 近くのpickupへ向かう
+    let to_pickup = pickup_query
+        .iter()
+        .map(|tf| tf.translation.xy() - pos)
+        .filter(|v| v.length_squared() < params.seek_radius * params.seek_radius)
+        .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .and_then(|v| v.try_normalize());
+
+    // 止まる場合も含めて一番scoreの高い方向
+    let prev_dir = DIRS.iter().position(|(_, btns)| {
+        MOVE_BTNS
+            .iter()
+            .all(|btn| btns.contains(btn) == input.pressed(*btn))
+    });
+    let stay_score = -density(pos) * params.danger_weight;
+    let mut best: (Option<usize>, f32) = (None, stay_score);
+    for (i, (dir, _)) in DIRS.iter().enumerate() {
+        let mut score = -density(pos + *dir * params.probe_distance) * params.danger_weight;
+        if let Some(to_pickup) = to_pickup {
+            score += dir.dot(to_pickup) * params.pickup_weight;
+        }
+        if prev_dir == Some(i) {
+            score += params.keep_weight;
+        }
+        if score > best.1 {
+            best = (Some(i), score);
+        }
+    }
+    let pressed: &[InputMngBtn] = best.0.map_or(&[], |i| DIRS[i].1);
+    for btn in MOVE_BTNS {
+        if pressed.contains(&btn) {
+            input.press(btn);
+        } else {
+            input.release(btn);
+        }
+    }
+}
+
+// 狙う敵を探す時の作業用
+#[derive(Default)]
+struct AimScratch {
+    nearest: Vec<(Entity, f32)>,
+    in_range: Vec<Entity>,
+}
+
+// 敵を狙う
+fn bot_aim_system(
+    params: Res<BotParams>,
+    shm: Res<SHM>,
+    mut found: Local<AimScratch>,
+    mut input: ResMut<ButtonInput<InputMngBtn>>,
+    mut aim: ResMut<AimInput>,
+    pl_query: Query<&Transform, With<Player>>,
+    ene_query: Query<(&Transform, &Health), (With<Enemy>, Without<Inactive>)>,
+) {
+    let Ok(pl_tf) = pl_query.get_single() else {
+        return;
+    };
+    let pos = pl_tf.translation.xy();
+    let position = |e: Entity| {
+        ene_query
+            .get(e)
+            .ok()
+            .filter(|(_, health)| health.hp > 0.)
+            .map(|(tf, _)| tf.translation.xy())
+    };
+    let AimScratch { nearest, in_range } = &mut *found;
+    shm.sg2
+        .k_nearest_into(pos, 1, AIM_SEARCH_RADIUS, &position, nearest);
+    let nearest = nearest.first().and_then(|(e, _)| position(*e));
+    let target = match params.target {
+        BotTarget::Nearest => nearest,
+        BotTarget::Weakest => {
+            shm.sg2.radius_into(pos, AIM_RANGE, &position, in_range);
+            in_range
+                .iter()
+                .filter_map(|e| ene_query.get(*e).ok())
+                .min_by(|a, b| a.1.hp.total_cmp(&b.1.hp))
+                .map(|(tf, _)| tf.translation.xy())
+                .or(nearest)
+        }
+    };
+    aim.pos = target;
+    if aim.pos.is_some() {
+        input.press(InputMngBtn::Shot);
+    } else {
+        input.release(InputMngBtn::Shot);
+    }
+}
+
+// titleで放置されたらbotでdemo開始
+fn attract_idle_system(
+    time: Res<Time>,
+    mut idle: Local<f32>,
+    kb: Res<ButtonInput<KeyCode>>,
+    mb: Res<ButtonInput<MouseButton>>,
+    mut control: ResMut<BotControl>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if kb.get_pressed().next().is_some() || mb.get_pressed().next().is_some() {
+        *idle = 0.;
+        return;
+    }
+    *idle += time.delta_seconds();
+    if *idle >= ATTRACT_IDLE_SECONDS {
+        *idle = 0.;
+        control.active = true;
+        control.attract = true;
+        next_state.set(AppState::InGame);
+    }
+}
+
+// demo中に入力があるか,GameOverでtitleへ
+fn attract_exit_system(
+    state: Res<State<AppState>>,
+    kb: Res<ButtonInput<KeyCode>>,
+    mb: Res<ButtonInput<MouseButton>>,
+    mut control: ResMut<BotControl>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let input = kb.get_just_pressed().next().is_some() || mb.get_just_pressed().next().is_some();
+    if input || *state.get() == AppState::GameOver {
+        *control = BotControl::default();
+        next_state.set(AppState::Title);
+    }
+}
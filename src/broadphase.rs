@@ -0,0 +1,154 @@
+use crate::{
+    dense_grid::DenseGrid2d,
+    sparse_grid::{Aabb, SparseGrid2d},
+};
+use bevy::prelude::{Entity, Vec2};
+
+/// Dense gridの大きさ(cell数),playerを中心にこの範囲を持つ
+pub const DENSE_GRID_CELLS: usize = 160;
+/// k_nearest_intoで最初に調べる半径
+const KNN_START_RADIUS: f32 = 32.;
+
+/// Broadphaseの共通interface
+pub trait Broadphase2d: std::fmt::Debug + Send + Sync {
+    /// Remove all entities, keeping allocations
+    fn soft_clear(&mut self);
+    /// Move the covered area, for bounded backends (call right after `soft_clear`)
+    fn recenter(&mut self, _center: Vec2) {}
+    /// Insert an entity in the given Aabb coordinates
+    fn insert_aabb(&mut self, aabb: Aabb, entity: Entity);
+    /// Move an entity to `new_aabb`, `old_aabb` is a hint and an entity not in the grid is
+    /// inserted
+    ///
+    /// The sparse grid only touches the cells that changed, the dense grid rebuilds its cells in
+    /// `finish` when anything moved.
+    fn update(&mut self, entity: Entity, old_aabb: Aabb, new_aabb: Aabb);
+    /// Remove an entity, does nothing if it is not in the grid
+    fn remove(&mut self, entity: Entity);
+    /// Called once after all inserts, before any query
+    fn finish(&mut self) {}
+    /// Writes the entities in the cells covered by `aabb` into `out`, sorted and without duplicates
+    fn query_aabb_into(&self, aabb: Aabb, out: &mut Vec<Entity>);
+    /// Writes every pair sharing a cell into `out`, once each as `(a, b)` with `a < b`
+    fn pairs_into(&self, out: &mut Vec<(Entity, Entity)>);
+    /// Calls `f` for the entities in the cells covered by `aabb`, may repeat an entity
+    fn for_each_in_aabb(&self, aabb: Aabb, f: &mut dyn FnMut(Entity));
+    /// Writes the entities in the cells crossed by the segment into `out`, in cell order from
+    /// `start`, each once
+    fn segment_into(&self, start: Vec2, end: Vec2, out: &mut Vec<Entity>);
+
+    /// Same as `segment_into`, from `origin` along `dir` for `max_dist`
+    fn ray_into(&self, origin: Vec2, dir: Vec2, max_dist: f32, out: &mut Vec<Entity>) {
+        self.segment_into(origin, origin + dir.normalize_or_zero() * max_dist, out);
+    }
+
+    /// Writes the entities whose position is within `radius` of `pos` into `out`, sorted
+    ///
+    /// The grid only knows cells, `position` gives the entity position (None to skip it).
+    fn radius_into(
+        &self,
+        pos: Vec2,
+        radius: f32,
+        position: &dyn Fn(Entity) -> Option<Vec2>,
+        out: &mut Vec<Entity>,
+    ) {
+        out.clear();
+        self.for_each_in_aabb(Aabb::from_circle(pos, radius), &mut |e| {
+            if position(e).is_some_and(|p| p.distance_squared(pos) <= radius * radius) {
+                out.push(e);
+            }
+        });
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    /// Writes up to `k` entities nearest to `pos` (within `max_radius`) into `out` as
+    /// `(entity, distance)`, nearest first
+    ///
+    /// Searches a small area first and doubles it until `k` are found.
+    fn k_nearest_into(
+        &self,
+        pos: Vec2,
+        k: usize,
+        max_radius: f32,
+        position: &dyn Fn(Entity) -> Option<Vec2>,
+        out: &mut Vec<(Entity, f32)>,
+    ) {
+        out.clear();
+        if k == 0 {
+            return;
+        }
+        let mut radius = KNN_START_RADIUS.min(max_radius);
+        loop {
+            out.clear();
+            self.for_each_in_aabb(Aabb::from_circle(pos, radius), &mut |e| {
+                if let Some(d) = position(e).map(|p| p.distance(pos)) {
+                    if d <= radius {
+                        out.push((e, d));
+                    }
+                }
+            });
+            // 同じentityは同じ距離なので隣り合う
+            out.sort_unstable_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            out.dedup_by_key(|(e, _)| *e);
+            // 範囲内は全部見つかっているので,k個あればそれが一番近い
+            if out.len() >= k || radius >= max_radius {
+                break;
+            }
+            radius = (radius * 2.).min(max_radius);
+        }
+        out.truncate(k);
+    }
+}
+
+impl<const TILE_SIZE: usize> Broadphase2d for SparseGrid2d<TILE_SIZE> {
+    fn soft_clear(&mut self) {
+        SparseGrid2d::soft_clear(self);
+    }
+    fn insert_aabb(&mut self, aabb: Aabb, entity: Entity) {
+        SparseGrid2d::insert_aabb(self, aabb, entity);
+    }
+    fn update(&mut self, entity: Entity, old_aabb: Aabb, new_aabb: Aabb) {
+        SparseGrid2d::update(self, entity, old_aabb, new_aabb);
+    }
+    fn remove(&mut self, entity: Entity) {
+        SparseGrid2d::remove(self, entity);
+    }
+    fn query_aabb_into(&self, aabb: Aabb, out: &mut Vec<Entity>) {
+        SparseGrid2d::query_aabb_into(self, aabb, out);
+    }
+    fn pairs_into(&self, out: &mut Vec<(Entity, Entity)>) {
+        SparseGrid2d::pairs_into(self, out);
+    }
+    fn for_each_in_aabb(&self, aabb: Aabb, f: &mut dyn FnMut(Entity)) {
+        self.aabb_iter(aabb).for_each(f);
+    }
+    fn segment_into(&self, start: Vec2, end: Vec2, out: &mut Vec<Entity>) {
+        out.clear();
+        for e in self.segment_iter(start, end) {
+            // 通る順,数は少ないので線形探索
+            if !out.contains(&e) {
+                out.push(e);
+            }
+        }
+    }
+}
+
+// 使うbroadphase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BroadphaseKind {
+    #[default]
+    Sparse, //HashMap,範囲の制限無し,訪れたcellは残り続ける
+    Dense, //player中心の固定範囲,範囲外は1つずつ調べる
+}
+impl BroadphaseKind {
+    pub fn create<const TILE_SIZE: usize>(self) -> Box<dyn Broadphase2d> {
+        match self {
+            BroadphaseKind::Sparse => Box::<SparseGrid2d<TILE_SIZE>>::default(),
+            BroadphaseKind::Dense => Box::new(DenseGrid2d::<TILE_SIZE>::new(
+                DENSE_GRID_CELLS,
+                DENSE_GRID_CELLS,
+            )),
+        }
+    }
+}
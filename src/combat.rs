@@ -0,0 +1,209 @@
+use crate::{
+    components::*,
+    enemy::EnemyCount,
+    game_rng::{GameRng, RngStream},
+    physics::SHM,
+    pickup,
+    pool::{EntityPool, Inactive, Pooled},
+    shape,
+    stats::RunStats,
+    AppState,
+};
+use bevy::prelude::*;
+
+// 弾,敵の当たり判定,damageでの退場,GameOver判定
+pub struct CombatPlugin;
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (bullet_vs_enemy_system, enemy_vs_player_system)
+                .in_set(GameSystemSet::UpdatePhysics)
+                .run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                check_game_over_system.before(update_entity_existence_system),
+                update_entity_existence_system,
+            )
+                .in_set(GameSystemSet::PostUpdate)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// 弾の当たり判定の作業用
+#[derive(Default)]
+pub struct BulletHitScratch {
+    candidates: Vec<Entity>,
+    hits: Vec<(f32, Entity)>,
+}
+
+// 1frameの移動の線分で判定,速い弾がすり抜けないように
+pub fn bullet_vs_enemy_system(
+    mut bullet_query: Query<
+        (&Transform, &PhysicalObj, &HitCircle, &mut DamageSource),
+        (With<FromPlayer>, Without<Inactive>),
+    >,
+    mut ene_query: Query<
+        (&Transform, &CollideCircle, &mut Health),
+        (With<Enemy>, Without<Inactive>),
+    >,
+    shm: Res<SHM>,
+    mut scratch: Local<BulletHitScratch>,
+) {
+    let BulletHitScratch { candidates, hits } = &mut *scratch;
+    for (tf0, obj0, hit0, mut dmg0) in bullet_query.iter_mut() {
+        if dmg0.damage <= 0. {
+            continue;
+        }
+        let p0 = obj0.old_pos;
+        let p1 = tf0.translation.xy();
+        let path = p1 - p0;
+        let collider = hit0.collider(tf0);
+        let swept = collider.swept(-path);
+        // 通った範囲全体のcell,線分から大きさ分はみ出した敵も入る
+        shm.sg2.query_aabb_into(swept.aabb(), candidates);
+
+        hits.clear();
+        for &e1 in candidates.iter() {
+            if let Ok((tf1, colli1, health1)) = ene_query.get(e1) {
+                if health1.hp <= 0. || !hit0.filter.overlaps(&colli1.filter) {
+                    continue;
+                }
+                if shape::contact(&swept, &colli1.collider(tf1)).is_some() {
+                    // 経路上の位置,中心を線分に射影
+                    let t = (tf1.translation.xy() - p0).dot(path) / path.length_squared();
+                    hits.push((if t.is_finite() { t.clamp(0., 1.) } else { 0. }, e1));
+                }
+            }
+        }
+        // 経路上の近い順にdamage
+        hits.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        for &(_, e1) in hits.iter() {
+            if dmg0.damage <= 0. {
+                break;
+            }
+            if let Ok((_, _, mut health1)) = ene_query.get_mut(e1) {
+                let health = health1.hp;
+                health1.hp -= dmg0.damage;
+                dmg0.damage -= health;
+            }
+        }
+    }
+}
+
+// 敵に触れるとdamage,無敵時間あり
+fn enemy_vs_player_system(
+    time: Res<Time>,
+    mut pl_query: Query<(&Transform, &CollideCircle, &mut Health, &mut Invincible), With<Player>>,
+    ene_query: Query<(&Transform, &CollideCircle), (With<Enemy>, Without<Inactive>)>,
+    shm: Res<SHM>,
+    mut stats: ResMut<RunStats>,
+    mut hits: Local<Vec<Entity>>,
+) {
+    let Ok((tf0, colli0, mut health, mut invincible)) = pl_query.get_single_mut() else {
+        return;
+    };
+    invincible.0.tick(time.delta());
+    if !invincible.0.finished() {
+        return;
+    }
+    let collider0 = colli0.collider(tf0);
+    shm.sg2.query_aabb_into(collider0.aabb(), &mut hits);
+    for &e1 in hits.iter() {
+        if let Ok((tf1, colli1)) = ene_query.get(e1) {
+            if colli0.filter.overlaps(&colli1.filter)
+                && shape::contact(&collider0, &colli1.collider(tf1)).is_some()
+            {
+                health.hp -= 1.;
+                stats.add_damage(1.);
+                invincible.0.reset();
+                break;
+            }
+        }
+    }
+}
+
+pub fn update_entity_existence_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut pool: ResMut<EntityPool>,
+    mut game_rng: ResMut<GameRng>,
+    mut stats: ResMut<RunStats>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&mut Lifetime>,
+            Option<&Health>,
+            Option<&DamageSource>,
+            Option<&Enemy>,
+            Option<&Pooled>,
+        ),
+        Without<Inactive>,
+    >,
+) {
+    // poolで管理していればpoolに戻す
+    let remove =
+        |commands: &mut Commands, pool: &mut EntityPool, entity, pooled: Option<&Pooled>| {
+            match pooled {
+                Some(pooled) => pool.release(commands, entity, pooled.0),
+                None => commands.entity(entity).despawn(),
+            }
+        };
+    for (entity, tf, timer, health, dmg, enemy, pooled) in query.iter_mut() {
+        // 生存時間
+        if let Some(mut timer) = timer {
+            timer.0.tick(time.delta());
+            if timer.0.finished() {
+                remove(&mut commands, &mut pool, entity, pooled);
+                if enemy.is_some() {
+                    enemy_count.count -= 1;
+                }
+                continue;
+            }
+        }
+        // 体力
+        if let Some(health) = health {
+            if health.hp <= 0. {
+                remove(&mut commands, &mut pool, entity, pooled);
+                if enemy.is_some() {
+                    enemy_count.count -= 1;
+                    stats.add_kill();
+                    pickup::drop_pickup(
+                        &mut commands,
+                        &mut pool,
+                        game_rng.stream(RngStream::Loot),
+                        tf.translation.xy(),
+                    );
+                }
+                continue;
+            }
+        }
+        // damage
+        if let Some(dmg) = dmg {
+            if dmg.damage <= 0. {
+                remove(&mut commands, &mut pool, entity, pooled);
+                if enemy.is_some() {
+                    enemy_count.count -= 1;
+                }
+                continue;
+            }
+        }
+    }
+}
+
+// playerがやられたらGameOver
+fn check_game_over_system(
+    q_player: Query<&Health, With<Player>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if let Ok(health) = q_player.get_single() {
+        if health.hp <= 0. {
+            next_state.set(AppState::GameOver);
+        }
+    }
+}
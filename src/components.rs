@@ -1,3 +1,6 @@
+use crate::data_asset::DataAsset;
+use crate::shape::{Collider, Shape};
+use crate::sparse_grid::Aabb;
 use bevy::math::Vec2;
 use bevy::prelude::*;
 
@@ -22,7 +25,11 @@ pub struct PhysicalObj {
     pub old_move_vec: Vec2,
     pub force: Vec2,
     pub velocity: Vec2,
-    pub collision_count: u32,
+    pub collision_count: u32, //solverの接触数,押し合い中の速さの制限に使う
+    // Noneなら PhysicsConfig の値
+    pub restitution: Option<f32>, //反発係数,接触した2つの大きい方を使う
+    pub friction: Option<f32>,    //摩擦係数
+    pub damping: Option<f32>,     //1秒後に残る速度の割合
 }
 impl Default for PhysicalObj {
     fn default() -> Self {
@@ -34,35 +41,149 @@ impl Default for PhysicalObj {
             force: Vec2::new(0., 0.),
             velocity: Vec2::new(0., 0.),
             collision_count: 0,
+            restitution: None,
+            friction: None,
+            damping: None,
         }
     }
 }
 
-// 衝突する,円
+// 衝突の層,1つのentityは1つの層に属する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum CollisionLayer {
+    Player,
+    Enemy,
+    PlayerShot,
+    EnemyShot,
+    Pickup,
+    Obstacle,
+}
+impl CollisionLayer {
+    pub const fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+// 層のbitmask,ronでは層のlistで書く
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(from = "Vec<CollisionLayer>")]
+pub struct CollisionMask(pub u32);
+impl CollisionMask {
+    pub const NONE: Self = Self(0);
+    pub const fn of(layers: &[CollisionLayer]) -> Self {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < layers.len() {
+            bits |= layers[i].bit();
+            i += 1;
+        }
+        Self(bits)
+    }
+    pub const fn contains(self, layer: CollisionLayer) -> bool {
+        self.0 & layer.bit() != 0
+    }
+}
+impl From<Vec<CollisionLayer>> for CollisionMask {
+    fn from(layers: Vec<CollisionLayer>) -> Self {
+        Self::of(&layers)
+    }
+}
+
+// 属する層と,押し合う層(collide),当たり判定する層(overlap)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub struct CollisionFilter {
+    pub layer: CollisionLayer,
+    pub collide: CollisionMask,
+    pub overlap: CollisionMask,
+}
+impl CollisionFilter {
+    // 層毎の標準のmask
+    pub const fn new(layer: CollisionLayer) -> Self {
+        use CollisionLayer::*;
+        let (collide, overlap) = match layer {
+            Player => (
+                CollisionMask::of(&[Enemy, Obstacle]),
+                CollisionMask::of(&[Enemy, EnemyShot, Pickup]),
+            ),
+            Enemy => (
+                CollisionMask::of(&[Player, Enemy, Obstacle]),
+                CollisionMask::of(&[Player]),
+            ),
+            PlayerShot => (CollisionMask::NONE, CollisionMask::of(&[Enemy, Obstacle])),
+            EnemyShot => (CollisionMask::NONE, CollisionMask::of(&[Player, Obstacle])),
+            Pickup => (CollisionMask::NONE, CollisionMask::of(&[Player])),
+            Obstacle => (CollisionMask::of(&[Player, Enemy]), CollisionMask::NONE),
+        };
+        Self {
+            layer,
+            collide,
+            overlap,
+        }
+    }
+    // 押し合うのは両方のcollideに相手の層がある時
+    pub fn collides(&self, other: &Self) -> bool {
+        self.collide.contains(other.layer) && other.collide.contains(self.layer)
+    }
+    // selfからotherへの当たり判定をするか
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.overlap.contains(other.layer)
+    }
+}
+
+// 衝突する,形はshapeで選ぶ
+// 名前は円だった頃のまま,shape::Colliderと紛れるのでCollider等には変えない
 #[derive(Component)]
 pub struct CollideCircle {
-    pub radius: f32,
+    pub radius: f32, //Circle以外では角の丸み
+    pub shape: Shape,
+    pub filter: CollisionFilter,
 }
-impl Default for CollideCircle {
-    fn default() -> Self {
-        Self { radius: 4. }
+impl CollideCircle {
+    // 層は必ず決める,半径4の円
+    pub fn new(layer: CollisionLayer) -> Self {
+        Self {
+            radius: 4.,
+            shape: Shape::Circle,
+            filter: CollisionFilter::new(layer),
+        }
+    }
+    pub fn collider(&self, tf: &Transform) -> Collider {
+        Collider::new(self.shape, self.radius, tf)
     }
 }
 
-// 接触判定する,円
+// broadphaseに登録したaabb,変わった時だけ登録しなおす
+#[derive(Component, Clone, Copy)]
+pub struct GridAabb(pub Aabb);
+
+// 接触判定する,CollideCircleと同じく名前は円のまま
 #[derive(Component)]
 pub struct HitCircle {
-    pub radius: f32,
+    pub radius: f32, //Circle以外では角の丸み
+    pub shape: Shape,
+    pub filter: CollisionFilter,
 }
-impl Default for HitCircle {
-    fn default() -> Self {
-        Self { radius: 3. }
+impl HitCircle {
+    // 層は必ず決める,半径3の円
+    pub fn new(layer: CollisionLayer) -> Self {
+        Self {
+            radius: 3.,
+            shape: Shape::Circle,
+            filter: CollisionFilter::new(layer),
+        }
+    }
+    pub fn collider(&self, tf: &Transform) -> Collider {
+        Collider::new(self.shape, self.radius, tf)
     }
 }
 
 #[derive(Component)]
 pub struct Lifetime(pub Timer);
 
+// 被弾後の無敵時間
+#[derive(Component)]
+pub struct Invincible(pub Timer);
+
 // 体力,0でdespawn
 #[derive(Component)]
 pub struct Health {
@@ -103,6 +224,12 @@ pub struct Player;
 #[derive(Component)]
 pub struct Enemy;
 
+// 拾うと経験値
+#[derive(Component)]
+pub struct Pickup {
+    pub exp: u32,
+}
+
 #[derive(Component)]
 pub struct FromPlayer;
 
@@ -119,14 +246,62 @@ impl Default for DamageSource {
     }
 }
 
+// 武器の性能,player.weapon.ronから読む
+#[derive(Resource, Asset, TypePath, Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct WeaponParams {
+    pub interval: f32, //連射の間隔(sec)
+    pub bullet_speed: f32,
+    pub damage: f32,
+    pub lifetime: f32, //弾が消えるまで(sec)
+}
+impl Default for WeaponParams {
+    fn default() -> Self {
+        Self {
+            interval: 1. / 60. * 4.,
+            bullet_speed: 150.,
+            damage: 1.,
+            lifetime: 1.,
+        }
+    }
+}
+impl DataAsset for WeaponParams {
+    fn validate(&self, errors: &mut Vec<String>) {
+        let values = [
+            ("interval", self.interval),
+            ("bullet_speed", self.bullet_speed),
+            ("damage", self.damage),
+            ("lifetime", self.lifetime),
+        ];
+        for (name, value) in values {
+            if value <= 0. {
+                errors.push(format!("{name} must be positive"));
+            }
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Weapon {
     pub repeat: Timer,
+    pub params: WeaponParams,
 }
-impl Default for Weapon {
-    fn default() -> Self {
+impl Weapon {
+    pub fn new(params: &WeaponParams) -> Self {
         Self {
-            repeat: Timer::from_seconds(1. / 60. * 4., TimerMode::Repeating),
+            repeat: Timer::from_seconds(params.interval, TimerMode::Repeating),
+            params: params.clone(),
         }
     }
+    // 性能の変更,連射の経過時間はそのまま
+    pub fn set_params(&mut self, params: &WeaponParams) {
+        self.repeat
+            .set_duration(std::time::Duration::from_secs_f32(params.interval));
+        self.params = params.clone();
+    }
+}
+impl Default for Weapon {
+    fn default() -> Self {
+        Self::new(&WeaponParams::default())
+    }
 }
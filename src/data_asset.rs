@@ -0,0 +1,416 @@
+/*! bevy_common_assets | MIT License | https://github.com/NiklasEi/bevy_common_assets/blob/main/LICENSE-MIT */
+//https://github.com/NiklasEi/bevy_common_assets/blob/main/src/ron.rs
+
+use bevy::asset::io::Reader;
+use bevy::asset::{
+    AssetApp, AssetLoadError, AssetLoadFailedEvent, AssetLoader, AssetPath, AsyncReadExt,
+    BoxedFuture, ErasedLoadedAsset, LoadContext, RecursiveDependencyLoadState,
+};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+pub const ASSET_DIR: &str = "assets";
+
+/// A data file asset, loaded from ron, json or toml by the extension
+pub trait DataAsset: Asset + DeserializeOwned {
+    /// Other data files to load with this one, relative to this file's folder
+    fn includes(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Takes a loaded include, called in the order of [`DataAsset::includes`]
+    fn include(&mut self, _path: &AssetPath, _asset: ErasedLoadedAsset) -> Result<(), String> {
+        Ok(())
+    }
+    /// Semantic checks after the includes, pushes one message per problem
+    fn validate(&self, errors: &mut Vec<String>);
+}
+
+/// File format of a data asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Ron,
+    Json,
+    Toml,
+}
+impl DataFormat {
+    /// By the last extension, `game.level.toml` is toml
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ron" => Some(Self::Ron),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+    pub fn parse<A: DeserializeOwned>(self, bytes: &[u8]) -> Result<A, DataLoaderError> {
+        match self {
+            Self::Ron => Ok(ron::de::from_bytes(bytes)?),
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+            Self::Toml => {
+                let text = std::str::from_utf8(bytes)?;
+                toml::from_str(text).map_err(|error| {
+                    // spanはbyte位置,行と列にする
+                    let position = error.span().map(|span| {
+                        let before = &text[..span.start];
+                        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                        (
+                            before.matches('\n').count() + 1,
+                            before[line_start..].chars().count() + 1,
+                        )
+                    });
+                    DataLoaderError::TomlError { error, position }
+                })
+            }
+        }
+    }
+}
+
+/// A parse or validation error of a data asset
+#[derive(Debug, Clone)]
+pub struct DataAssetError {
+    pub path: String,
+    /// Line and column, only parse errors have it
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+/// Errors of the loaded data assets, an entry is removed when the file is fixed
+#[derive(Resource, Debug, Default)]
+pub struct DataAssetErrors {
+    entries: Vec<(String, DataAssetError)>,
+}
+impl DataAssetErrors {
+    /// Replaces the errors of the asset loaded from `path`, they may be in its includes
+    pub fn set(&mut self, path: &str, errors: Vec<DataAssetError>) {
+        self.entries.retain(|(p, _)| p != path);
+        for e in errors.iter() {
+            warn!("{}: {}", e.path, e.message);
+        }
+        self.entries
+            .extend(errors.into_iter().map(|e| (path.to_string(), e)));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &DataAssetError> {
+        self.entries.iter().map(|(_, e)| e)
+    }
+}
+
+// ファイルごとのincludeしたファイル,loaderが書いて循環を見る
+#[derive(Resource, Clone, Default)]
+struct DataIncludes(Arc<Mutex<HashMap<AssetPath<'static>, Vec<AssetPath<'static>>>>>);
+impl DataIncludes {
+    // pathのincludeを書き換えて,辿るとpathに戻るincludeを返す
+    fn update(
+        &self,
+        path: &AssetPath<'static>,
+        includes: &[AssetPath<'static>],
+    ) -> Vec<AssetPath<'static>> {
+        let mut map = self.0.lock().unwrap();
+        map.insert(path.clone(), includes.to_vec());
+        let leads_back = |include: &AssetPath<'static>| {
+            let mut visited = HashSet::new();
+            let mut paths = vec![include];
+            while let Some(p) = paths.pop() {
+                if p == path {
+                    return true;
+                }
+                if visited.insert(p) {
+                    paths.extend(map.get(p).into_iter().flatten());
+                }
+            }
+            false
+        };
+        includes.iter().filter(|p| leads_back(p)).cloned().collect()
+    }
+}
+
+/// Plugin to load your asset type `A` from ron, json or toml files.
+pub struct DataAssetPlugin<A> {
+    extensions: Vec<&'static str>,
+    _marker: PhantomData<A>,
+}
+
+impl<A: DataAsset> Plugin for DataAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        // 更新はasset serverのfile watcherが見る,includeしたファイルが変わればincludeした側も読み直す
+        app.init_resource::<DataIncludes>()
+            .init_resource::<DataAssetErrors>();
+        let includes = app.world.resource::<DataIncludes>().clone();
+        app.init_asset::<A>()
+            .register_asset_loader(DataAssetLoader::<A> {
+                extensions: self.extensions.clone(),
+                includes,
+                _marker: PhantomData,
+            })
+            .add_systems(Update, track_data_asset_system::<A>);
+    }
+}
+
+impl<A: DataAsset> DataAssetPlugin<A> {
+    /// Create a new plugin that will load assets from files with the given extensions,
+    /// the last part of each extension is the format, like `level.ron`.
+    pub fn new(extensions: &[&'static str]) -> Self {
+        Self {
+            extensions: extensions.to_owned(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+struct DataAssetLoader<A> {
+    extensions: Vec<&'static str>,
+    includes: DataIncludes,
+    _marker: PhantomData<A>,
+}
+
+/// Possible errors that can be produced by [`DataAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DataLoaderError {
+    /// An [IO Error](std::io::Error)
+    #[error("Could not read the file: {0}")]
+    Io(#[from] std::io::Error),
+    /// The extension is not ron, json or toml
+    #[error("Unknown data format: {0}")]
+    UnknownFormat(String),
+    /// A [RON Error](serde_ron::error::SpannedError)
+    #[error("Could not parse RON: {0}")]
+    RonError(#[from] ron::error::SpannedError),
+    /// A [JSON Error](serde_json::Error)
+    #[error("Could not parse JSON: {0}")]
+    JsonError(#[from] serde_json::Error),
+    /// A toml file is not utf-8
+    #[error("Could not read TOML: {0}")]
+    Utf8Error(#[from] std::str::Utf8Error),
+    /// A [TOML Error](toml::de::Error)
+    #[error("Could not parse TOML: {error}")]
+    TomlError {
+        error: toml::de::Error,
+        position: Option<(usize, usize)>,
+    },
+    /// An included file could not be loaded, the errors are of that file
+    #[error("Could not include: {}", .0.iter().map(|e| e.message.as_str()).collect::<Vec<_>>().join(", "))]
+    Include(Vec<DataAssetError>),
+    /// Parsed, but [`DataAsset::validate`] found problems
+    #[error("Invalid: {}", .0.join(", "))]
+    Invalid(Vec<String>),
+}
+
+impl DataLoaderError {
+    fn to_asset_errors(&self, path: &str) -> Vec<DataAssetError> {
+        let error = |position, message| DataAssetError {
+            path: path.into(),
+            position,
+            message,
+        };
+        match self {
+            Self::RonError(e) => vec![error(
+                Some((e.position.line, e.position.col)),
+                e.code.to_string(),
+            )],
+            Self::JsonError(e) => vec![error(Some((e.line(), e.column())), e.to_string())],
+            Self::TomlError { error: e, position } => {
+                vec![error(*position, e.message().to_string())]
+            }
+            Self::Include(errors) => errors.clone(),
+            Self::Invalid(messages) => messages.iter().map(|m| error(None, m.clone())).collect(),
+            e => vec![error(None, e.to_string())],
+        }
+    }
+}
+
+// loaderのerrorなら位置も出す
+fn load_errors(path: &AssetPath, error: &AssetLoadError) -> Vec<DataAssetError> {
+    let path = path.to_string();
+    if let AssetLoadError::AssetLoaderError { error, .. } = error {
+        if let Some(e) = error.downcast_ref::<DataLoaderError>() {
+            return e.to_asset_errors(&path);
+        }
+    }
+    vec![DataAssetError {
+        path,
+        position: None,
+        message: error.to_string(),
+    }]
+}
+
+impl<A: DataAsset> AssetLoader for DataAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = DataLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let format = DataFormat::from_path(load_context.path()).ok_or_else(|| {
+                DataLoaderError::UnknownFormat(load_context.path().display().to_string())
+            })?;
+            let mut asset: A = format.parse(&bytes)?;
+
+            // includeはこのファイルのfolderから.load_directでdependencyになる
+            let path = load_context.asset_path().clone();
+            let mut errors = Vec::new();
+            let mut includes = Vec::new();
+            for include in asset.includes() {
+                match path.resolve_embed(&include) {
+                    Ok(p) => includes.push(p),
+                    Err(e) => errors.push(format!("{include}: {e}")),
+                }
+            }
+            // 循環していたらload_directが終わらない
+            for p in self.includes.update(&path, &includes) {
+                errors.push(format!("{p}: includes {path}"));
+            }
+            if !errors.is_empty() {
+                return Err(DataLoaderError::Invalid(errors));
+            }
+            for include in includes {
+                let loaded = load_context
+                    .load_direct(include.clone())
+                    .await
+                    .map_err(|e| DataLoaderError::Include(load_errors(&include, &e.error)))?;
+                if let Err(e) = asset.include(&include, loaded) {
+                    errors.push(format!("{include}: {e}"));
+                }
+            }
+
+            asset.validate(&mut errors);
+            if !errors.is_empty() {
+                return Err(DataLoaderError::Invalid(errors));
+            }
+            Ok(asset)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+// handleのassetが読めたか,更新されたか
+pub fn changed<A: Asset>(events: &mut EventReader<AssetEvent<A>>, handle: &Handle<A>) -> bool {
+    let mut changed = false;
+    for event in events.read() {
+        changed |= matches!(event, AssetEvent::Added { id } | AssetEvent::Modified { id }
+            if *id == handle.id());
+    }
+    changed
+}
+
+/// Whether the asset and its includes finished loading, or failed
+pub fn is_load_finished<A: Asset>(asset_server: &AssetServer, handle: &Handle<A>) -> bool {
+    matches!(
+        asset_server.recursive_dependency_load_state(handle),
+        RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed
+    )
+}
+
+// 読めたらerrorを消す,読めなければerrorを残す
+fn track_data_asset_system<A: Asset>(
+    asset_server: Res<AssetServer>,
+    mut loaded: EventReader<AssetEvent<A>>,
+    mut failed: EventReader<AssetLoadFailedEvent<A>>,
+    mut errors: ResMut<DataAssetErrors>,
+) {
+    for event in loaded.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            if let Some(path) = asset_server.get_path(*id) {
+                errors.set(&path.to_string(), Vec::new());
+            }
+        }
+    }
+    for event in failed.read() {
+        errors.set(
+            &event.path.to_string(),
+            load_errors(&event.path, &event.error),
+        );
+    }
+}
+
+// testでassetsのファイルをloaderと同じ形式で読む,includeは手で渡す
+#[cfg(test)]
+pub(crate) fn load_asset_file<A: DataAsset>(path: &str) -> A {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join(ASSET_DIR)
+        .join(path);
+    let format = DataFormat::from_path(&path).unwrap();
+    format.parse(&std::fs::read(path).unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::slice::from_ref;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Data {
+        name: String,
+        values: Vec<f32>,
+    }
+
+    #[test]
+    fn parse_by_format() {
+        let expected = Data {
+            name: "slime".into(),
+            values: vec![1., 2.],
+        };
+        let sources = [
+            ("a.level.ron", r#"(name: "slime", values: [1., 2.])"#),
+            ("a.level.json", r#"{"name": "slime", "values": [1.0, 2.0]}"#),
+            ("a.level.toml", "name = \"slime\"\nvalues = [1.0, 2.0]"),
+        ];
+        for (path, text) in sources {
+            let format = DataFormat::from_path(Path::new(path)).unwrap();
+            let data: Data = format.parse(text.as_bytes()).unwrap();
+            assert_eq!(data, expected, "{path}");
+        }
+        assert_eq!(DataFormat::from_path(Path::new("a.level.yaml")), None);
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let sources = [
+            (DataFormat::Ron, "(\n  name: 1,\n)"),
+            (DataFormat::Json, "{\n  \"name\": 1\n}"),
+            (DataFormat::Toml, "\nname = 1\n"),
+        ];
+        for (format, text) in sources {
+            let error = format.parse::<Data>(text.as_bytes()).unwrap_err();
+            let errors = error.to_asset_errors("a");
+            assert_eq!(errors.len(), 1);
+            let (line, _) = errors[0].position.unwrap();
+            assert_eq!(line, 2, "{format:?} {errors:?}");
+        }
+    }
+
+    #[test]
+    fn include_cycle() {
+        let includes = DataIncludes::default();
+        let path = |p: &str| AssetPath::from(p.to_string());
+        let (a, b, c) = (
+            path("a.level.ron"),
+            path("b.enemies.ron"),
+            path("c.enemies.ron"),
+        );
+        // aがbとcを,bがcをincludeするのは循環しない
+        assert!(includes.update(&a, &[b.clone(), c.clone()]).is_empty());
+        assert!(includes.update(&b, from_ref(&c)).is_empty());
+        assert!(includes.update(&c, &[]).is_empty());
+        // cがaをincludeするとa->b->c->a
+        assert_eq!(includes.update(&c, from_ref(&a)), from_ref(&a));
+        assert_eq!(includes.update(&a, from_ref(&a)), from_ref(&a));
+    }
+}
@@ -0,0 +1,10 @@
+use crate::{dw_gui::DwGuiPlugin, show_debug::ShowDebugPlugin, show_fps::ShowFpsPlugin};
+use bevy::prelude::*;
+
+// 当たり判定の表示,fps,調整用のgui
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((ShowDebugPlugin, ShowFpsPlugin, DwGuiPlugin));
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    broadphase::Broadphase2d,
+    sparse_grid::{Aabb, SegmentKeyIter},
+};
+use bevy::{
+    ecs::entity::EntityHashMap,
+    prelude::{Entity, Vec2},
+};
+
+/// A bounded uniform grid, rebuilt with a counting sort in `finish` when anything changed
+///
+/// Covers `width * height` cells around a movable center. Entities reaching outside are
+/// also kept in an overflow list and checked one by one, so queries give the same result as
+/// `SparseGrid2d` but get slower there.
+/// Memory is bounded by the cell count and the number of inserted entities.
+#[derive(Debug, Clone)]
+pub struct DenseGrid2d<const TILE_SIZE: usize = 1> {
+    width: i32,
+    height: i32,
+    origin: (i32, i32),                 //左下のcellのkey
+    bodies: EntityHashMap<Aabb>,        //insertされたentity,centerが動くのでcellでは持たない
+    items: Vec<(u32, Entity)>,          //(cell, entity)
+    cell_start: Vec<u32>,               //cell毎のentriesの開始位置,最後に終端
+    cursor: Vec<u32>,                   //counting sortの書き込み位置
+    entries: Vec<Entity>,               //cell順に並べたentity
+    overflow: Vec<(CellRange, Entity)>, //範囲外にかかるentity,範囲はgridの外のkeyのまま
+    dirty: bool,                        //前のfinishから変わった
+}
+
+/// Inclusive cell range `(x0, y0, x1, y1)`
+type CellRange = (i32, i32, i32, i32);
+
+fn overlaps(a: CellRange, b: CellRange) -> bool {
+    a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+}
+
+impl<const TILE_SIZE: usize> DenseGrid2d<TILE_SIZE> {
+    pub fn new(width: usize, height: usize) -> Self {
+        let cells = width * height;
+        Self {
+            width: width as i32,
+            height: height as i32,
+            origin: (-(width as i32) / 2, -(height as i32) / 2),
+            bodies: EntityHashMap::default(),
+            items: Vec::new(),
+            cell_start: vec![0; cells + 1],
+            cursor: vec![0; cells],
+            entries: Vec::new(),
+            overflow: Vec::new(),
+            dirty: true,
+        }
+    }
+
+    /// Cell range covered by the Aabb, relative to `origin`
+    ///
+    /// Uses the same cells as `SparseGrid2d` (`floor(min)..ceil(max)`).
+    fn cell_range(&self, aabb: Aabb) -> Option<CellRange> {
+        let s = TILE_SIZE as f32;
+        let x0 = (aabb.min.x / s).floor() as i32 - self.origin.0;
+        let y0 = (aabb.min.y / s).floor() as i32 - self.origin.1;
+        let x1 = (aabb.max.x / s).ceil() as i32 - 1 - self.origin.0;
+        let y1 = (aabb.max.y / s).ceil() as i32 - 1 - self.origin.1;
+        (x0 <= x1 && y0 <= y1).then_some((x0, y0, x1, y1))
+    }
+
+    /// The part of the range inside the grid
+    fn clip(&self, (x0, y0, x1, y1): CellRange) -> Option<CellRange> {
+        let range = (
+            x0.max(0),
+            y0.max(0),
+            x1.min(self.width - 1),
+            y1.min(self.height - 1),
+        );
+        (range.0 <= range.2 && range.1 <= range.3).then_some(range)
+    }
+
+    fn is_inside(&self, range: CellRange) -> bool {
+        self.clip(range) == Some(range)
+    }
+
+    fn cells(&self, aabb: Aabb) -> impl Iterator<Item = usize> + '_ {
+        let range = self.cell_range(aabb).and_then(|r| self.clip(r));
+        range.into_iter().flat_map(move |(x0, y0, x1, y1)| {
+            (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (y * self.width + x) as usize))
+        })
+    }
+
+    fn cell(&self, index: usize) -> &[Entity] {
+        let start = self.cell_start[index] as usize;
+        let end = self.cell_start[index + 1] as usize;
+        &self.entries[start..end]
+    }
+
+    // 範囲外にかかるentityのうち,rangeと重なるもの
+    fn overflow_in(&self, range: Option<CellRange>) -> impl Iterator<Item = Entity> + '_ {
+        let hit = move |r: &&(CellRange, Entity)| range.is_some_and(|range| overlaps(r.0, range));
+        self.overflow.iter().filter(hit).map(|(_, e)| *e)
+    }
+}
+
+impl<const TILE_SIZE: usize> Broadphase2d for DenseGrid2d<TILE_SIZE> {
+    fn soft_clear(&mut self) {
+        self.bodies.clear();
+        self.dirty = true;
+    }
+
+    fn recenter(&mut self, center: Vec2) {
+        let s = TILE_SIZE as f32;
+        let origin = (
+            (center.x / s).floor() as i32 - self.width / 2,
+            (center.y / s).floor() as i32 - self.height / 2,
+        );
+        self.dirty |= origin != self.origin;
+        self.origin = origin;
+    }
+
+    fn insert_aabb(&mut self, aabb: Aabb, entity: Entity) {
+        self.bodies.insert(entity, aabb);
+        self.dirty = true;
+    }
+
+    fn update(&mut self, entity: Entity, _old_aabb: Aabb, new_aabb: Aabb) {
+        self.bodies.insert(entity, new_aabb);
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        self.dirty |= self.bodies.remove(&entity).is_some();
+    }
+
+    fn finish(&mut self) {
+        // 何も動いていなければ前のまま
+        if !std::mem::take(&mut self.dirty) {
+            return;
+        }
+        self.items.clear();
+        self.overflow.clear();
+        for (&entity, &aabb) in &self.bodies {
+            let Some(range) = self.cell_range(aabb) else {
+                continue;
+            };
+            if !self.is_inside(range) {
+                self.overflow.push((range, entity));
+            }
+            let Some((x0, y0, x1, y1)) = self.clip(range) else {
+                continue;
+            };
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    self.items.push(((y * self.width + x) as u32, entity));
+                }
+            }
+        }
+        // counting sort
+        self.cell_start.fill(0);
+        for &(cell, _) in &self.items {
+            self.cell_start[cell as usize + 1] += 1;
+        }
+        for i in 1..self.cell_start.len() {
+            self.cell_start[i] += self.cell_start[i - 1];
+        }
+        let cells = self.cursor.len();
+        self.cursor.copy_from_slice(&self.cell_start[..cells]);
+        self.entries.clear();
+        self.entries.resize(self.items.len(), Entity::PLACEHOLDER);
+        for &(cell, entity) in &self.items {
+            let at = &mut self.cursor[cell as usize];
+            self.entries[*at as usize] = entity;
+            *at += 1;
+        }
+        // 範囲の順,pairs_intoで前から見る
+        self.overflow
+            .sort_unstable_by_key(|&(range, e)| (range.0, e));
+    }
+
+    fn query_aabb_into(&self, aabb: Aabb, out: &mut Vec<Entity>) {
+        out.clear();
+        for index in self.cells(aabb) {
+            out.extend_from_slice(self.cell(index));
+        }
+        out.extend(self.overflow_in(self.cell_range(aabb)));
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    fn for_each_in_aabb(&self, aabb: Aabb, f: &mut dyn FnMut(Entity)) {
+        for index in self.cells(aabb) {
+            self.cell(index).iter().copied().for_each(&mut *f);
+        }
+        self.overflow_in(self.cell_range(aabb)).for_each(f);
+    }
+
+    fn segment_into(&self, start: Vec2, end: Vec2, out: &mut Vec<Entity>) {
+        out.clear();
+        for (x, y) in SegmentKeyIter::new::<TILE_SIZE>(start, end) {
+            let (x, y) = (x - self.origin.0, y - self.origin.1);
+            // 通る順,数は少ないので線形探索
+            let mut push = |e: Entity| {
+                if !out.contains(&e) {
+                    out.push(e);
+                }
+            };
+            if self.is_inside((x, y, x, y)) {
+                self.cell((y * self.width + x) as usize)
+                    .iter()
+                    .copied()
+                    .for_each(&mut push);
+            } else {
+                self.overflow_in(Some((x, y, x, y))).for_each(push);
+            }
+        }
+    }
+
+    fn pairs_into(&self, out: &mut Vec<(Entity, Entity)>) {
+        out.clear();
+        for index in 0..self.cursor.len() {
+            let cell = self.cell(index);
+            for (i, &a) in cell.iter().enumerate() {
+                for &b in &cell[i + 1..] {
+                    if a != b {
+                        out.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        // 範囲外で重なるもの,中で重なるものは上で数えている
+        for (i, &(r0, a)) in self.overflow.iter().enumerate() {
+            for &(r1, b) in self.overflow[i + 1..]
+                .iter()
+                .take_while(|(r1, _)| r1.0 <= r0.2)
+            {
+                if overlaps(r0, r1) {
+                    out.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse_grid::SparseGrid2d;
+    use bevy::math::vec2;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const TILE_SIZE: usize = 10;
+
+    fn random_aabbs(count: u32, half: f32) -> Vec<(Aabb, Entity)> {
+        let mut rng = StdRng::seed_from_u64(1);
+        (0..count)
+            .map(|i| {
+                let p = vec2(rng.gen_range(-half..half), rng.gen_range(-half..half));
+                (
+                    Aabb::from_circle(p, rng.gen_range(1.0..6.0)),
+                    Entity::from_raw(i),
+                )
+            })
+            .collect()
+    }
+
+    fn build(grid: &mut dyn Broadphase2d, items: &[(Aabb, Entity)]) {
+        grid.soft_clear();
+        for (aabb, entity) in items {
+            grid.insert_aabb(*aabb, *entity);
+        }
+        grid.finish();
+    }
+
+    #[test]
+    fn same_as_sparse_inside() {
+        let items = random_aabbs(500, 150.);
+        let mut sparse = SparseGrid2d::<TILE_SIZE>::default();
+        let mut dense = DenseGrid2d::<TILE_SIZE>::new(40, 40);
+        build(&mut sparse, &items);
+        build(&mut dense, &items);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        Broadphase2d::pairs_into(&sparse, &mut a);
+        dense.pairs_into(&mut b);
+        assert_eq!(a, b);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for (aabb, _) in &items {
+            Broadphase2d::query_aabb_into(&sparse, *aabb, &mut a);
+            dense.query_aabb_into(*aabb, &mut b);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn same_as_sparse_outside() {
+        // 範囲外はoverflowから,sparseと同じ結果
+        let items = random_aabbs(300, 400.);
+        let mut sparse = SparseGrid2d::<TILE_SIZE>::default();
+        let mut dense = DenseGrid2d::<TILE_SIZE>::new(8, 8);
+        build(&mut sparse, &items);
+        build(&mut dense, &items);
+        assert!(!dense.overflow.is_empty());
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        Broadphase2d::pairs_into(&sparse, &mut a);
+        dense.pairs_into(&mut b);
+        assert_eq!(a, b);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for (aabb, _) in &items {
+            Broadphase2d::query_aabb_into(&sparse, *aabb, &mut a);
+            dense.query_aabb_into(*aabb, &mut b);
+            assert_eq!(a, b);
+        }
+        sparse.segment_into(vec2(-390., -300.), vec2(350., 380.), &mut a);
+        dense.segment_into(vec2(-390., -300.), vec2(350., 380.), &mut b);
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn segment_same_as_sparse() {
+        let items = random_aabbs(500, 150.);
+        let mut sparse = SparseGrid2d::<TILE_SIZE>::default();
+        let mut dense = DenseGrid2d::<TILE_SIZE>::new(40, 40);
+        build(&mut sparse, &items);
+        build(&mut dense, &items);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        sparse.segment_into(vec2(-140., -90.), vec2(130., 110.), &mut a);
+        dense.segment_into(vec2(-140., -90.), vec2(130., 110.), &mut b);
+        assert!(!a.is_empty());
+        // cell内の順番は違ってもいい
+        a.sort_unstable();
+        b.sort_unstable();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn nearest_and_radius() {
+        let positions = [vec2(0., 0.), vec2(5., 0.), vec2(50., 0.), vec2(300., 0.)];
+        let items: Vec<(Aabb, Entity)> = positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (Aabb::from_circle(*p, 2.), Entity::from_raw(i as u32)))
+            .collect();
+        let position = |e: Entity| positions.get(e.index() as usize).copied();
+        let e = |i| Entity::from_raw(i);
+        let mut grids: [Box<dyn Broadphase2d>; 2] = [
+            Box::<SparseGrid2d<TILE_SIZE>>::default(),
+            Box::new(DenseGrid2d::<TILE_SIZE>::new(40, 40)),
+        ];
+        for grid in grids.iter_mut() {
+            build(grid.as_mut(), &items);
+
+            let mut near = Vec::new();
+            grid.k_nearest_into(vec2(4., 0.), 2, 1000., &position, &mut near);
+            assert_eq!(near, vec![(e(1), 1.), (e(0), 4.)]);
+            // 最初の範囲に無くても広げて探す
+            grid.k_nearest_into(vec2(4., 0.), 3, 1000., &position, &mut near);
+            assert_eq!(near.last(), Some(&(e(2), 46.)));
+            grid.k_nearest_into(vec2(4., 0.), 10, 100., &position, &mut near);
+            assert_eq!(near.len(), 3);
+
+            let mut found = Vec::new();
+            grid.radius_into(vec2(0., 0.), 10., &position, &mut found);
+            assert_eq!(found, vec![e(0), e(1)]);
+        }
+    }
+
+    #[test]
+    fn update_same_as_rebuild() {
+        let items = random_aabbs(300, 150.);
+        let moved = random_aabbs(300, 150.).into_iter().map(|(aabb, e)| {
+            let offset = vec2(e.index() as f32 % 7., -(e.index() as f32 % 13.));
+            let aabb = Aabb {
+                min: aabb.min + offset,
+                max: aabb.max + offset,
+            };
+            (aabb, e)
+        });
+        let moved: Vec<(Aabb, Entity)> = moved.collect();
+        let mut grids: [Box<dyn Broadphase2d>; 2] = [
+            Box::<SparseGrid2d<TILE_SIZE>>::default(),
+            Box::new(DenseGrid2d::<TILE_SIZE>::new(40, 40)),
+        ];
+        for grid in grids.iter_mut() {
+            build(grid.as_mut(), &items);
+            for ((old, e), (new, _)) in items.iter().zip(&moved) {
+                if e.index() % 5 == 0 {
+                    grid.remove(*e);
+                } else {
+                    grid.update(*e, *old, *new);
+                }
+            }
+            grid.finish();
+            let kept: Vec<(Aabb, Entity)> = moved
+                .iter()
+                .filter(|(_, e)| e.index() % 5 != 0)
+                .copied()
+                .collect();
+            let mut rebuilt = SparseGrid2d::<TILE_SIZE>::default();
+            build(&mut rebuilt, &kept);
+
+            let (mut a, mut b) = (Vec::new(), Vec::new());
+            grid.pairs_into(&mut a);
+            Broadphase2d::pairs_into(&rebuilt, &mut b);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn recenter_moves_area() {
+        let e = Entity::from_raw(1);
+        let far = Aabb::from_circle(vec2(1000., 1000.), 2.);
+        let mut dense = DenseGrid2d::<TILE_SIZE>::new(4, 4);
+        dense.recenter(vec2(1000., 1000.));
+        build(&mut dense, &[(far, e)]);
+
+        let mut out = Vec::new();
+        dense.query_aabb_into(far, &mut out);
+        assert_eq!(out, vec![e]);
+        // 同じcellに入っていない場所
+        dense.query_aabb_into(Aabb::from_circle(vec2(1015., 1015.), 1.), &mut out);
+        assert!(out.is_empty());
+    }
+}
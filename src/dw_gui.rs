@@ -1,4 +1,13 @@
-use crate::{enemy::EnemyCount, GameConfig};
+use crate::{
+    broadphase::BroadphaseKind,
+    data_asset::DataAssetErrors,
+    enemy::{ArchetypeId, EnemyArchetypes, EnemyCount, EnemyLeash},
+    enemy_behavior::FlockingParams,
+    physics::PhysicsResource,
+    physics_config::{PhysicsConfig, PhysicsConfigFile},
+    pool::Inactive,
+    GameConfig,
+};
 use bevy::prelude::*;
 use bevy::render::view::screenshot::ScreenshotManager;
 use bevy::window::PrimaryWindow;
@@ -11,7 +20,15 @@ impl Plugin for DwGuiPlugin {
         app.add_plugins(EguiPlugin)
             // Systems that create Egui widgets should be run during the `CoreSet::Update` set,
             // or after the `EguiSet::BeginFrame` system (which belongs to the `CoreSet::PreUpdate` set).
-            .add_systems(Update, common_debug_ui_system);
+            .add_systems(
+                Update,
+                (
+                    common_debug_ui_system,
+                    enemy_debug_ui_system,
+                    physics_debug_ui_system,
+                    asset_error_ui_system,
+                ),
+            );
     }
 }
 
@@ -53,3 +70,89 @@ fn common_debug_ui_system(
         });
     });
 }
+
+// 種類毎の敵の数,群れの調整
+fn enemy_debug_ui_system(
+    mut contexts: EguiContexts,
+    archetypes: Res<EnemyArchetypes>,
+    mut flocking: ResMut<FlockingParams>,
+    mut leash: ResMut<EnemyLeash>,
+    enemy_query: Query<&ArchetypeId, Without<Inactive>>,
+) {
+    let mut counts = vec![0u32; archetypes.list.len()];
+    for id in enemy_query.iter() {
+        if let Some(c) = counts.get_mut(id.0) {
+            *c += 1;
+        }
+    }
+    egui::Window::new("enemy")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (archetype, c) in archetypes.list.iter().zip(counts) {
+                ui.label(format!("{0}:{1}", archetype.name, c));
+            }
+            ui.collapsing("flocking", |ui| {
+                ui.add(egui::Slider::new(&mut flocking.separation, 0.0..=4.0).text("separation"));
+                ui.add(egui::Slider::new(&mut flocking.alignment, 0.0..=4.0).text("alignment"));
+                ui.add(egui::Slider::new(&mut flocking.cohesion, 0.0..=4.0).text("cohesion"));
+                ui.add(egui::Slider::new(&mut flocking.speed, 0.0..=60.0).text("speed"));
+            });
+            ui.add(egui::Slider::new(&mut leash.distance, 200.0..=2000.0).text("leash"));
+        });
+}
+
+// 物理の調整,ファイルが更新されたら上書きされる
+fn physics_debug_ui_system(
+    mut contexts: EguiContexts,
+    mut config: ResMut<PhysicsConfig>,
+    file: Res<PhysicsConfigFile>,
+    asset_server: Res<AssetServer>,
+    mut physics_resource: ResMut<PhysicsResource>,
+) {
+    egui::Window::new("physics")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.add(egui::Slider::new(&mut config.damping, 0.0..=1.0).text("damping"));
+            ui.add(egui::Slider::new(&mut config.restitution, 0.0..=1.0).text("restitution"));
+            ui.add(egui::Slider::new(&mut config.friction, 0.0..=2.0).text("friction"));
+            ui.add(egui::Slider::new(&mut config.solver_iterations, 1..=16).text("iterations"));
+            ui.add(egui::Slider::new(&mut config.solver_relaxation, 1.0..=2.0).text("relaxation"));
+            ui.add(egui::Slider::new(&mut config.crowd_max_speed, 0.0..=600.0).text("crowd speed"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut physics_resource.parallel_collision, "parallel");
+                let broadphase = &mut physics_resource.broadphase;
+                ui.radio_value(broadphase, BroadphaseKind::Sparse, "sparse");
+                ui.radio_value(broadphase, BroadphaseKind::Dense, "dense");
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Reset").clicked() {
+                    *config = PhysicsConfig::default();
+                }
+                if ui.button("Reload").clicked() {
+                    file.reload(&asset_server);
+                }
+                if ui.button("Save").clicked() {
+                    if let Err(e) = file.save(&config) {
+                        warn!("{e}");
+                    }
+                }
+            });
+        });
+}
+
+// data fileのparse,検証のerror,ファイルを直せば消える
+fn asset_error_ui_system(mut contexts: EguiContexts, errors: Res<DataAssetErrors>) {
+    if errors.is_empty() {
+        return;
+    }
+    egui::Window::new("asset errors").show(contexts.ctx_mut(), |ui| {
+        for e in errors.iter() {
+            let at = match e.position {
+                Some((line, col)) => format!("{}:{line}:{col}", e.path),
+                None => e.path.clone(),
+            };
+            ui.colored_label(egui::Color32::LIGHT_RED, at);
+            ui.label(&e.message);
+        }
+    });
+}
@@ -1,7 +1,25 @@
-use crate::{components::*, AppState, GameTextures};
-use bevy::{prelude::*, time::common_conditions::on_timer};
+use crate::{
+    components::*,
+    data_asset::DataAsset,
+    enemy_behavior::{
+        enemy_flocking_system, enemy_steering_system, EnemyBehavior, FlockingParams, Steering,
+        SteeringState,
+    },
+    fixed_step::every_ticks,
+    game_rng::{GameRng, RngStream},
+    pool::{EntityPool, Inactive, PoolKind},
+    shape::Shape,
+    sprite_atlas::SpriteAtlas,
+    wave::GameSequence,
+    AppState,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
 use rand::Rng;
-use std::{f32::consts::PI, time::Duration};
+use smallvec::SmallVec;
+use std::f32::consts::PI;
+
+const ENEMY_SPRITE_SCALE: f32 = 1.25; //8pxのspriteを10pxで描く
+const SPAWN_INTERVAL_TICKS: u64 = 2; //menuの時間に左右されないようにtickで数える
 
 #[derive(Resource)]
 pub struct EnemyCount {
@@ -17,76 +35,313 @@ impl Default for EnemyCount {
     }
 }
 
+// 敵の種類
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub hp: f32,
+    pub radius: f32,
+    #[serde(default)]
+    pub shape: Shape, //大きい敵は箱など
+    pub speed: f32,
+    pub steerings: Vec<(Steering, f32)>,
+    pub spawn_weight: f32, //出現しやすさ
+    #[serde(default = "enemy_collision_filter")]
+    pub collision: CollisionFilter, //押し合う層,当たり判定する層
+    #[serde(default = "enemy_sprite")]
+    pub sprite: String, //atlasの中の名前
+}
+
+fn enemy_collision_filter() -> CollisionFilter {
+    CollisionFilter::new(CollisionLayer::Enemy)
+}
+fn enemy_sprite() -> String {
+    "slime_walk_0".into()
+}
+impl EnemyArchetype {
+    fn behavior(&self) -> EnemyBehavior {
+        EnemyBehavior {
+            steerings: SmallVec::from_slice(&self.steerings),
+            speed: self.speed,
+        }
+    }
+}
+
+// enemies.ronから読む,読めるまではdefault
+#[derive(Resource, Asset, TypePath, Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct EnemyArchetypes {
+    pub list: Vec<EnemyArchetype>,
+}
+impl Default for EnemyArchetypes {
+    fn default() -> Self {
+        let archetype = |name: &str, hp: f32, speed: f32, steerings: &[(Steering, f32)], w: f32| {
+            EnemyArchetype {
+                name: name.into(),
+                hp,
+                radius: 4.,
+                shape: Shape::Circle,
+                speed,
+                steerings: steerings.to_vec(),
+                spawn_weight: w,
+                collision: enemy_collision_filter(),
+                sprite: enemy_sprite(),
+            }
+        };
+        Self {
+            list: vec![
+                archetype("slime", 1., 18., &[(Steering::Chase, 1.)], 10.),
+                archetype("bat", 1., 26., &[(Steering::Orbit { radius: 40. }, 1.)], 2.),
+                archetype(
+                    "imp",
+                    2.,
+                    22.,
+                    &[
+                        (Steering::Flee { hp_ratio: 0.5 }, 1.),
+                        (Steering::Flank { angle: 1.2 }, 1.),
+                    ],
+                    2.,
+                ),
+                archetype(
+                    "boar",
+                    3.,
+                    14.,
+                    &[(
+                        Steering::Charge {
+                            range: 60.,
+                            windup: 0.6,
+                            dash_time: 0.4,
+                            dash_speed: 5.,
+                        },
+                        1.,
+                    )],
+                    1.,
+                ),
+                EnemyArchetype {
+                    // 敵同士はすり抜ける
+                    collision: CollisionFilter {
+                        collide: CollisionMask::of(&[
+                            CollisionLayer::Player,
+                            CollisionLayer::Obstacle,
+                        ]),
+                        ..enemy_collision_filter()
+                    },
+                    ..archetype(
+                        "wisp",
+                        1.,
+                        16.,
+                        &[
+                            (Steering::Wander { jitter: 6. }, 1.),
+                            (Steering::Chase, 0.5),
+                        ],
+                        2.,
+                    )
+                },
+                archetype(
+                    "archer",
+                    2.,
+                    20.,
+                    &[(Steering::KeepDistance { distance: 80. }, 1.)],
+                    1.,
+                ),
+            ],
+        }
+    }
+}
+impl EnemyArchetypes {
+    // 出現の重みで選ぶ,allowedでないものは出ない
+    fn pick(&self, rng: &mut impl Rng, allowed: impl Fn(&EnemyArchetype) -> bool) -> Option<usize> {
+        let weight = |a: &EnemyArchetype| {
+            if allowed(a) {
+                a.spawn_weight.max(0.)
+            } else {
+                0.
+            }
+        };
+        let total: f32 = self.list.iter().map(weight).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut v = rng.gen_range(0. ..total);
+        for (i, a) in self.list.iter().enumerate() {
+            v -= weight(a);
+            if v < 0. {
+                return Some(i);
+            }
+        }
+        self.list.iter().rposition(allowed)
+    }
+}
+impl DataAsset for EnemyArchetypes {
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.list.iter().all(|a| a.spawn_weight <= 0.) {
+            errors.push("no archetype can spawn".into());
+        }
+        for (i, a) in self.list.iter().enumerate() {
+            let mut error = |message: &str| errors.push(format!("{} ({i}): {message}", a.name));
+            if a.name.is_empty() {
+                error("name is empty");
+            } else if self.list[..i].iter().any(|b| b.name == a.name) {
+                error("name is used twice");
+            }
+            if a.hp <= 0. {
+                error("hp must be positive");
+            }
+            if a.radius < 0. {
+                error("radius must not be negative");
+            }
+            if a.speed < 0. {
+                error("speed must not be negative");
+            }
+            if a.spawn_weight < 0. {
+                error("spawn_weight must not be negative");
+            }
+            if a.steerings.is_empty() {
+                error("steerings is empty");
+            }
+            if a.sprite.is_empty() {
+                error("sprite is empty");
+            }
+        }
+    }
+}
+
+// waveごとに出る敵の名前,levelから読む.空なら全部出る
+#[derive(Resource, Debug, Clone, Default)]
+pub struct EnemyWaves {
+    pub waves: Vec<Vec<String>>,
+}
+impl EnemyWaves {
+    // 最後のwaveを繰り返す
+    fn allows(&self, wave_no: u32, archetype: &EnemyArchetype) -> bool {
+        match self.waves.get(wave_no as usize).or(self.waves.last()) {
+            Some(names) => names.contains(&archetype.name),
+            None => true,
+        }
+    }
+}
+
+// 今のwaveで出る敵から選ぶ
+#[derive(SystemParam)]
+struct SpawnTable<'w> {
+    archetypes: Res<'w, EnemyArchetypes>,
+    waves: Res<'w, EnemyWaves>,
+    game_sequence: Res<'w, GameSequence>,
+}
+impl SpawnTable<'_> {
+    fn pick(&self, rng: &mut impl Rng) -> Option<usize> {
+        let wave_no = self.game_sequence.wave_no;
+        self.archetypes.pick(rng, |a| self.waves.allows(wave_no, a))
+    }
+}
+
+// playerから離れすぎた敵を前方に出し直す
+#[derive(Resource, Debug, Clone)]
+pub struct EnemyLeash {
+    pub distance: f32,    //これより離れたら出し直す
+    pub respawn_min: f32, //出し直す距離
+    pub respawn_max: f32,
+    pub half_angle: f32, //進行方向からの角度
+}
+impl Default for EnemyLeash {
+    fn default() -> Self {
+        Self {
+            distance: 700.,
+            respawn_min: 300.,
+            respawn_max: 600.,
+            half_angle: PI / 3.,
+        }
+    }
+}
+
+// EnemyArchetypes.listのindex
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ArchetypeId(pub usize);
+
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(EnemyCount { ..default() })
+            .init_resource::<EnemyArchetypes>()
+            .init_resource::<EnemyWaves>()
+            .init_resource::<FlockingParams>()
+            .init_resource::<EnemyLeash>()
+            .init_resource::<SpriteAtlas>()
             .add_systems(
-                Update,
-                enemy_movement_system
+                FixedUpdate,
+                (enemy_steering_system, enemy_flocking_system)
+                    .chain()
                     .in_set(GameSystemSet::Update)
                     .run_if(in_state(AppState::InGame)),
             )
             .add_systems(
-                Update,
-                enemy_spawn_system
+                FixedUpdate,
+                (enemy_leash_system, enemy_spawn_system)
+                    .chain()
                     .in_set(GameSystemSet::PostUpdate)
-                    .run_if(on_timer(Duration::from_secs_f32(2. / 60.)))
+                    .after(crate::combat::update_entity_existence_system)
+                    .run_if(every_ticks(SPAWN_INTERVAL_TICKS))
                     .run_if(in_state(AppState::InGame)),
             );
     }
 }
 
-fn random_circle_base(r0: f32, ed_r: f32, half_central_ang: f32) -> Vec2 {
-    let mut rng = rand::thread_rng();
+fn random_circle_base(rng: &mut impl Rng, r0: f32, ed_r: f32, half_central_ang: f32) -> Vec2 {
     let r = rng.gen_range(r0..1.).sqrt() * ed_r;
     let theta = rng.gen_range(-half_central_ang..half_central_ang);
     Vec2::new(r * theta.cos(), r * theta.sin())
 }
 
-fn random_circle(st_r: f32, ed_r: f32) -> Vec2 {
+fn random_circle(rng: &mut impl Rng, st_r: f32, ed_r: f32) -> Vec2 {
     let r0 = st_r / ed_r;
-    random_circle_base(r0, ed_r, PI)
+    random_circle_base(rng, r0, ed_r, PI)
 }
 
 fn enemy_spawn_system(
     mut commands: Commands,
     mut enemy_count: ResMut<EnemyCount>,
-    game_textures: Res<GameTextures>,
+    mut pool: ResMut<EntityPool>,
+    mut game_rng: ResMut<GameRng>,
+    spawn_table: SpawnTable,
+    sprite_atlas: Res<SpriteAtlas>,
     q_player: Query<&Transform, With<Player>>,
 ) {
     let Ok(pl_tf) = q_player.get_single() else {
         return;
     };
+    let rng = game_rng.stream(RngStream::Spawn);
     for _ in 0..100 {
         if enemy_count.count < enemy_count.max {
-            let pos = random_circle(100., 600.) + pl_tf.translation.xy();
-            let _entity_id = commands
-                .spawn(SpriteSheetBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(10., 10.)),
+            let Some(id) = spawn_table.pick(rng) else {
+                break;
+            };
+            let archetype = &spawn_table.archetypes.list[id];
+            let pos = random_circle(rng, 100., 600.) + pl_tf.translation.xy();
+            pool.spawn(
+                &mut commands,
+                PoolKind::Enemy,
+                (
+                    sprite_atlas.bundle(
+                        &archetype.sprite,
+                        ENEMY_SPRITE_SCALE,
+                        Transform::from_translation(pos.extend(5.)),
+                    ),
+                    Enemy,
+                    PhysicalObj {
+                        old_pos: pos,
                         ..default()
                     },
-                    atlas: TextureAtlas {
-                        layout: game_textures.spr0_layout.clone(),
-                        index: 0,
+                    CollideCircle {
+                        radius: archetype.radius,
+                        shape: archetype.shape,
+                        filter: archetype.collision,
                     },
-                    texture: game_textures.spr0_tex.clone(),
-                    transform: Transform {
-                        translation: pos.extend(5.),
-                        ..default()
-                    },
-                    ..default()
-                })
-                .insert(Enemy)
-                .insert(PhysicalObj {
-                    old_pos: pos,
-                    ..default()
-                })
-                .insert(CollideCircle { ..default() })
-                .insert(Health::from_max(1.))
-                .id();
+                    Health::from_max(archetype.hp),
+                    ArchetypeId(id),
+                    archetype.behavior(),
+                    SteeringState::new(rng),
+                ),
+            );
 
             enemy_count.count += 1;
         } else {
@@ -95,18 +350,35 @@ fn enemy_spawn_system(
     }
 }
 
-fn enemy_movement_system(
-    q_player: Query<&Transform, With<Player>>,
-    time: Res<Time>,
-    mut q_enemy: Query<(&Transform, &mut PhysicalObj), With<Enemy>>,
+// 離れすぎた敵はarchetype,体力そのままでplayerの進行方向に移す
+fn enemy_leash_system(
+    leash: Res<EnemyLeash>,
+    mut game_rng: ResMut<GameRng>,
+    q_player: Query<(&Transform, &PhysicalObj), With<Player>>,
+    mut q_enemy: Query<
+        (&mut Transform, &mut PhysicalObj),
+        (With<Enemy>, Without<Player>, Without<Inactive>),
+    >,
 ) {
-    // playerに近づく
-    let Ok(pl_tf) = q_player.get_single() else {
+    let Ok((pl_tf, pl_obj)) = q_player.get_single() else {
         return;
     };
-    let dt = time.delta_seconds();
-    for (ene_tf, mut obj) in q_enemy.iter_mut() {
-        let dir = (pl_tf.translation.xy() - ene_tf.translation.xy()).normalize_or_zero();
-        obj.move_vec += dir * 18. * dt;
+    let pl_pos = pl_tf.translation.xy();
+    let sqr_distance = leash.distance * leash.distance;
+    let (r0, half_angle) = (leash.respawn_min / leash.respawn_max, leash.half_angle);
+    let forward = pl_obj.velocity.try_normalize();
+    let rng = game_rng.stream(RngStream::Spawn);
+    for (mut tf, mut obj) in q_enemy.iter_mut() {
+        if tf.translation.xy().distance_squared(pl_pos) <= sqr_distance {
+            continue;
+        }
+        let offset = match forward {
+            Some(dir) => dir.rotate(random_circle_base(rng, r0, leash.respawn_max, half_angle)),
+            None => random_circle(rng, leash.respawn_min, leash.respawn_max),
+        };
+        let pos = pl_pos + offset;
+        tf.translation = pos.extend(tf.translation.z);
+        obj.old_pos = pos;
+        obj.velocity = Vec2::ZERO;
     }
 }
@@ -0,0 +1,298 @@
+use crate::{
+    components::*,
+    game_rng::{GameRng, RngStream},
+    physics::SHM,
+    pool::Inactive,
+    sparse_grid::Aabb,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use smallvec::SmallVec;
+
+// 敵の移動挙動,archetype毎に組み合わせて使う
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+pub enum Steering {
+    // playerに直進
+    Chase,
+    // 角度つけて横から回り込む,近づくとChaseになる
+    Flank {
+        angle: f32,
+    },
+    // playerの周りを回る
+    Orbit {
+        radius: f32,
+    },
+    // range内に入ったら溜めてから突進
+    Charge {
+        range: f32,
+        windup: f32,
+        dash_time: f32,
+        dash_speed: f32,
+    },
+    // 体力の割合がhp_ratio以下で逃げる
+    Flee {
+        hp_ratio: f32,
+    },
+    // うろうろ
+    Wander {
+        jitter: f32,
+    },
+    // playerとの距離を保つ
+    KeepDistance {
+        distance: f32,
+    },
+}
+
+// 挙動の組み合わせ,(挙動,重み)
+// 止まる/突進/逃げるは他を無視する,複数ある時は先に書いたもの優先
+#[derive(Component, Debug, Clone)]
+pub struct EnemyBehavior {
+    pub steerings: SmallVec<[(Steering, f32); 4]>,
+    pub speed: f32,
+}
+impl Default for EnemyBehavior {
+    fn default() -> Self {
+        Self {
+            steerings: SmallVec::from_slice(&[(Steering::Chase, 1.)]),
+            speed: 18.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum ChargePhase {
+    #[default]
+    Approach,
+    Windup,
+    Dash,
+}
+
+// 挙動毎の状態
+#[derive(Component, Debug, Clone)]
+pub struct SteeringState {
+    phase: ChargePhase,
+    phase_time: f32,
+    dash_dir: Vec2,
+    wander_angle: f32,
+    side: f32, //回り込む向き,1 or -1
+}
+impl SteeringState {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        Self {
+            phase: ChargePhase::Approach,
+            phase_time: 0.,
+            dash_dir: Vec2::ZERO,
+            wander_angle: rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI),
+            side: if rng.gen_bool(0.5) { 1. } else { -1. },
+        }
+    }
+}
+
+// 1つの挙動の出力
+enum SteerOut {
+    // 重み付きで合成する方向
+    Blend(Vec2),
+    // 他を無視してこの移動量(速度倍率込み)を使う
+    Exclusive(Vec2),
+}
+
+fn steer(
+    steering: &Steering,
+    state: &mut SteeringState,
+    rng: &mut impl Rng,
+    to_player: Vec2,
+    hp_ratio: f32,
+    dt: f32,
+) -> SteerOut {
+    let dist = to_player.length();
+    let dir = to_player.normalize_or_zero();
+    match *steering {
+        Steering::Chase => SteerOut::Blend(dir),
+        Steering::Flank { angle } => {
+            // 遠いほど大きく回り込む
+            let t = (dist / 200.).min(1.);
+            SteerOut::Blend(Vec2::from_angle(angle * state.side * t).rotate(dir))
+        }
+        Steering::Orbit { radius } => {
+            let tangent = dir.perp() * state.side;
+            let radial = dir * ((dist - radius) / radius.max(1.)).clamp(-1., 1.);
+            SteerOut::Blend((tangent + radial).normalize_or_zero())
+        }
+        Steering::Charge {
+            range,
+            windup,
+            dash_time,
+            dash_speed,
+        } => {
+            state.phase_time += dt;
+            match state.phase {
+                ChargePhase::Approach => {
+                    if dist <= range {
+                        state.phase = ChargePhase::Windup;
+                        state.phase_time = 0.;
+                        return SteerOut::Exclusive(Vec2::ZERO);
+                    }
+                    SteerOut::Blend(dir)
+                }
+                ChargePhase::Windup => {
+                    // 溜め中は止まって,向きだけ追う
+                    state.dash_dir = dir;
+                    if state.phase_time >= windup {
+                        state.phase = ChargePhase::Dash;
+                        state.phase_time = 0.;
+                    }
+                    SteerOut::Exclusive(Vec2::ZERO)
+                }
+                ChargePhase::Dash => {
+                    if state.phase_time >= dash_time {
+                        state.phase = ChargePhase::Approach;
+                        state.phase_time = 0.;
+                    }
+                    SteerOut::Exclusive(state.dash_dir * dash_speed)
+                }
+            }
+        }
+        Steering::Flee { hp_ratio: th } => {
+            if hp_ratio <= th {
+                SteerOut::Exclusive(-dir)
+            } else {
+                SteerOut::Blend(Vec2::ZERO)
+            }
+        }
+        Steering::Wander { jitter } => {
+            state.wander_angle += rng.gen_range(-1.0..1.0) * jitter * dt;
+            SteerOut::Blend(Vec2::from_angle(state.wander_angle))
+        }
+        Steering::KeepDistance { distance } => {
+            let slack = distance * 0.1;
+            if dist < distance - slack {
+                SteerOut::Blend(-dir)
+            } else if dist > distance + slack {
+                SteerOut::Blend(dir)
+            } else {
+                SteerOut::Blend(dir.perp() * state.side * 0.5)
+            }
+        }
+    }
+}
+
+type SteeringQueryData<'a> = (
+    &'a Transform,
+    &'a EnemyBehavior,
+    &'a mut SteeringState,
+    Option<&'a Health>,
+    &'a mut PhysicalObj,
+);
+
+pub fn enemy_steering_system(
+    q_player: Query<&Transform, With<Player>>,
+    time: Res<Time>,
+    mut game_rng: ResMut<GameRng>,
+    mut q_enemy: Query<SteeringQueryData, (With<Enemy>, Without<Inactive>)>,
+) {
+    let Ok(pl_tf) = q_player.get_single() else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    let rng = game_rng.stream(RngStream::Ai);
+    for (ene_tf, behavior, mut state, health, mut obj) in q_enemy.iter_mut() {
+        let to_player = pl_tf.translation.xy() - ene_tf.translation.xy();
+        let hp_ratio = health.map_or(1., |h| h.get_ratio());
+        let mut blend = Vec2::ZERO;
+        let mut exclusive = None;
+        for (steering, weight) in behavior.steerings.iter() {
+            match steer(steering, &mut state, rng, to_player, hp_ratio, dt) {
+                SteerOut::Blend(v) => blend += v * *weight,
+                SteerOut::Exclusive(v) => {
+                    exclusive.get_or_insert(v);
+                }
+            }
+        }
+        let mov = match exclusive {
+            Some(v) => v,
+            None => blend.clamp_length_max(1.),
+        };
+        obj.move_vec += mov * behavior.speed * dt;
+    }
+}
+
+// 群れの挙動(boids)
+#[derive(Resource, Debug, Clone)]
+pub struct FlockingParams {
+    pub neighbor_radius: f32,   //近傍とみなす距離
+    pub separation_radius: f32, //離れようとする距離
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub speed: f32, //最大の移動量/秒
+}
+impl Default for FlockingParams {
+    fn default() -> Self {
+        Self {
+            neighbor_radius: 14.,
+            separation_radius: 9.,
+            separation: 1.5,
+            alignment: 0.3,
+            cohesion: 0.2,
+            speed: 20.,
+        }
+    }
+}
+
+pub fn enemy_flocking_system(
+    time: Res<Time>,
+    params: Res<FlockingParams>,
+    shm: Res<SHM>,
+    mut query: Query<(Entity, &Transform, &mut PhysicalObj), (With<Enemy>, Without<Inactive>)>,
+    mut steers: Local<Vec<(Entity, Vec2)>>,
+    mut neighbors: Local<Vec<Entity>>,
+) {
+    let dt = time.delta_seconds();
+    steers.clear();
+    for (e0, tf0, obj0) in query.iter() {
+        let pos0 = tf0.translation.xy();
+        let mut separation = Vec2::ZERO;
+        let mut velocity_sum = Vec2::ZERO;
+        let mut center_sum = Vec2::ZERO;
+        let mut count = 0;
+        shm.sg2.query_aabb_into(
+            Aabb::from_circle(pos0, params.neighbor_radius),
+            &mut neighbors,
+        );
+        for &e1 in neighbors.iter() {
+            if e0 == e1 {
+                continue;
+            }
+            let Ok((_, tf1, obj1)) = query.get(e1) else {
+                continue;
+            };
+            let diff = pos0 - tf1.translation.xy();
+            let d = diff.length();
+            if d > params.neighbor_radius {
+                continue;
+            }
+            if d > 0. && d < params.separation_radius {
+                // 近いほど強く離れる
+                separation += diff / d * (1. - d / params.separation_radius);
+            }
+            velocity_sum += obj1.velocity;
+            center_sum += tf1.translation.xy();
+            count += 1;
+        }
+        if count == 0 {
+            continue;
+        }
+        let inv_count = 1. / count as f32;
+        let alignment = (velocity_sum * inv_count - obj0.velocity).normalize_or_zero();
+        let cohesion = (center_sum * inv_count - pos0).normalize_or_zero();
+        let steer = separation * params.separation
+            + alignment * params.alignment
+            + cohesion * params.cohesion;
+        steers.push((e0, steer.clamp_length_max(1.)));
+    }
+    for (entity, steer) in steers.iter() {
+        if let Ok((_, _, mut obj)) = query.get_mut(*entity) {
+            obj.move_vec += *steer * params.speed * dt;
+        }
+    }
+}
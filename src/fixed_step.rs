@@ -0,0 +1,127 @@
+use crate::{components::PhysicalObj, AppState};
+use bevy::{prelude::*, transform::TransformSystem};
+
+// これ以上離れたらワープとみなして補間しない
+const SNAP_DISTANCE: f32 = 32.;
+
+// gameplay,physicsの更新頻度
+#[derive(Resource, Debug, Clone)]
+pub struct SimulationConfig {
+    pub tick_hz: f64,
+}
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self { tick_hz: 60. }
+    }
+}
+
+// runが始まってからのInGameのtick数,spawn,replayはこれで数える
+#[derive(Resource, Debug, Default)]
+pub struct RunTick(pub u64);
+
+// n tickに1回
+pub fn every_ticks(n: u64) -> impl FnMut(Res<RunTick>) -> bool + Clone {
+    move |tick: Res<RunTick>| tick.0.is_multiple_of(n)
+}
+
+// 描画用の補間,FixedUpdateの前後の位置
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Interpolated {
+    prev: Vec2,
+    curr: Vec2,
+}
+impl Interpolated {
+    pub fn new(pos: Vec2) -> Self {
+        Self {
+            prev: pos,
+            curr: pos,
+        }
+    }
+}
+
+pub struct FixedStepPlugin;
+
+impl Plugin for FixedStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationConfig>()
+            .init_resource::<RunTick>()
+            .add_systems(PreUpdate, apply_simulation_config_system)
+            // 状態の遷移はtickの間で,同じframeの残りのtickをInGameで回さない
+            .add_systems(
+                FixedFirst,
+                (
+                    apply_state_transition::<AppState>,
+                    restore_sim_transform_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedLast,
+                (
+                    store_sim_transform_system,
+                    init_interpolated_system,
+                    count_run_tick_system.run_if(in_state(AppState::InGame)),
+                ),
+            )
+            .add_systems(OnExit(AppState::Title), reset_run_tick_system)
+            .add_systems(
+                PostUpdate,
+                interpolate_transform_system.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn apply_simulation_config_system(
+    config: Res<SimulationConfig>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    if config.is_changed() && config.tick_hz > 0. {
+        fixed_time.set_timestep_hz(config.tick_hz);
+    }
+}
+
+fn reset_run_tick_system(mut tick: ResMut<RunTick>) {
+    tick.0 = 0;
+}
+
+fn count_run_tick_system(mut tick: ResMut<RunTick>) {
+    tick.0 += 1;
+}
+
+// 補間した位置を,simulationの位置に戻す
+fn restore_sim_transform_system(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut tf, mut interp) in query.iter_mut() {
+        tf.translation = interp.curr.extend(tf.translation.z);
+        interp.prev = interp.curr;
+    }
+}
+
+fn store_sim_transform_system(mut query: Query<(&Transform, &mut Interpolated)>) {
+    for (tf, mut interp) in query.iter_mut() {
+        interp.curr = tf.translation.xy();
+        if interp.prev.distance_squared(interp.curr) > SNAP_DISTANCE * SNAP_DISTANCE {
+            interp.prev = interp.curr; //pool再利用,leash等
+        }
+    }
+}
+
+fn init_interpolated_system(
+    mut commands: Commands,
+    query: Query<(Entity, &Transform), (With<PhysicalObj>, Without<Interpolated>)>,
+) {
+    for (entity, tf) in query.iter() {
+        commands
+            .entity(entity)
+            .insert(Interpolated::new(tf.translation.xy()));
+    }
+}
+
+pub fn interpolate_transform_system(
+    fixed_time: Res<Time<Fixed>>,
+    mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+    let t = fixed_time.overstep_fraction();
+    for (mut tf, interp) in query.iter_mut() {
+        tf.translation = interp.prev.lerp(interp.curr, t).extend(tf.translation.z);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    components::*,
+    data_asset::{changed, is_load_finished, DataAssetPlugin},
+    enemy::{EnemyArchetypes, EnemyWaves},
+    resources::GameLevel,
+};
+use bevy::prelude::*;
+
+const LEVEL_PATH: &str = "game.level.ron";
+const WEAPON_PATH: &str = "player.weapon.ron";
+
+// level,敵,武器のdata file.ron,json,tomlのどれでも書ける
+// 敵のファイルはlevelがincludeする.どれかが更新されたら読み直して反映する
+pub struct GameDataPlugin;
+impl Plugin for GameDataPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            DataAssetPlugin::<GameLevel>::new(&["level.ron", "level.json", "level.toml"]),
+            DataAssetPlugin::<EnemyArchetypes>::new(&[
+                "enemies.ron",
+                "enemies.json",
+                "enemies.toml",
+            ]),
+            DataAssetPlugin::<WeaponParams>::new(&["weapon.ron", "weapon.json", "weapon.toml"]),
+        ))
+        .add_systems(PreStartup, load_game_data_system)
+        .add_systems(Update, (apply_level_system, apply_weapon_params_system));
+    }
+}
+
+#[derive(Resource)]
+pub struct GameDataHandles {
+    pub level: Handle<GameLevel>,
+    pub weapon: Handle<WeaponParams>,
+}
+impl GameDataHandles {
+    /// Whether every file finished loading, a failed file keeps the current values
+    pub fn is_finished(&self, asset_server: &AssetServer) -> bool {
+        is_load_finished(asset_server, &self.level) && is_load_finished(asset_server, &self.weapon)
+    }
+}
+
+fn load_game_data_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(GameDataHandles {
+        level: asset_server.load(LEVEL_PATH),
+        weapon: asset_server.load(WEAPON_PATH),
+    });
+}
+
+// levelのwaveと,includeした敵の種類.名前はloaderで確かめてある
+fn apply_level_system(
+    handles: Res<GameDataHandles>,
+    mut events: EventReader<AssetEvent<GameLevel>>,
+    levels: Res<Assets<GameLevel>>,
+    mut archetypes: ResMut<EnemyArchetypes>,
+    mut waves: ResMut<EnemyWaves>,
+) {
+    if !changed(&mut events, &handles.level) {
+        return;
+    }
+    if let Some(level) = levels.get(&handles.level) {
+        info!("level loaded:{} enemies", level.archetypes.len());
+        archetypes.list = level.archetypes.clone();
+        waves.waves = level.waves.clone();
+    }
+}
+
+fn apply_weapon_params_system(
+    handles: Res<GameDataHandles>,
+    mut events: EventReader<AssetEvent<WeaponParams>>,
+    assets: Res<Assets<WeaponParams>>,
+    mut params: ResMut<WeaponParams>,
+    mut weapon_query: Query<&mut Weapon, With<ForPlayer>>,
+) {
+    if !changed(&mut events, &handles.weapon) {
+        return;
+    }
+    if let Some(loaded) = assets.get(&handles.weapon) {
+        info!("weapon loaded:{loaded:?}");
+        *params = loaded.clone();
+        for mut weapon in weapon_query.iter_mut() {
+            weapon.set_params(&params);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_asset::{load_asset_file, DataAsset, DataFormat};
+    use bevy::asset::{AssetPath, ErasedLoadedAsset, LoadedAsset};
+
+    const ENEMIES_PATH: &str = "game.enemies.ron";
+
+    fn validate(asset: &impl DataAsset) -> Vec<String> {
+        let mut errors = Vec::new();
+        asset.validate(&mut errors);
+        errors
+    }
+
+    fn include(level: &mut GameLevel, enemies: EnemyArchetypes) -> Result<(), String> {
+        let asset = ErasedLoadedAsset::from(LoadedAsset::from(enemies));
+        level.include(&AssetPath::from(ENEMIES_PATH), asset)
+    }
+
+    #[test]
+    fn assets_are_default() {
+        let enemies: EnemyArchetypes = load_asset_file(ENEMIES_PATH);
+        assert!(validate(&enemies).is_empty());
+        assert_eq!(enemies, EnemyArchetypes::default());
+        let weapon: WeaponParams = load_asset_file(WEAPON_PATH);
+        assert!(validate(&weapon).is_empty());
+        assert_eq!(weapon, WeaponParams::default());
+    }
+
+    // assetはronだけ,jsonとtomlでも同じ値を書ける
+    #[test]
+    fn weapon_in_other_formats() {
+        let sources = [
+            (
+                DataFormat::Json,
+                r#"{"interval": 0.06666667, "bullet_speed": 150.0, "damage": 1.0, "lifetime": 1.0}"#,
+            ),
+            (
+                DataFormat::Toml,
+                "interval = 0.06666667\nbullet_speed = 150.0\ndamage = 1.0\nlifetime = 1.0",
+            ),
+        ];
+        for (format, text) in sources {
+            let weapon: WeaponParams = format.parse(text.as_bytes()).unwrap();
+            assert_eq!(weapon, WeaponParams::default(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn level_includes_enemies() {
+        let mut level: GameLevel = load_asset_file(LEVEL_PATH);
+        assert_eq!(level.includes(), [ENEMIES_PATH]);
+        assert!(!validate(&level).is_empty()); //includeするまで敵を知らない
+        include(&mut level, load_asset_file(ENEMIES_PATH)).unwrap();
+        assert_eq!(validate(&level), Vec::<String>::new());
+        assert_eq!(level.archetypes, EnemyArchetypes::default().list);
+
+        // 同じ敵を2回includeした
+        include(&mut level, EnemyArchetypes::default()).unwrap();
+        assert_eq!(
+            validate(&level).len(),
+            EnemyArchetypes::default().list.len()
+        );
+        let weapon = ErasedLoadedAsset::from(LoadedAsset::from(WeaponParams::default()));
+        assert!(level
+            .include(&AssetPath::from(WEAPON_PATH), weapon)
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_enemies() {
+        let mut enemies = EnemyArchetypes::default();
+        enemies.list[0].hp = 0.;
+        enemies.list[1].name = enemies.list[2].name.clone();
+        assert_eq!(validate(&enemies).len(), 2);
+
+        let mut level: GameLevel = DataFormat::Json
+            .parse(br#"{"positions": [], "enemies": ["e.enemies.ron"], "waves": [["slime", "dragon"], []]}"#)
+            .unwrap();
+        include(&mut level, EnemyArchetypes::default()).unwrap();
+        let errors = validate(&level);
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[0].contains("dragon"));
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// 用途毎の乱数列,他の用途の呼び出し回数に影響されない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    Spawn,
+    Ai,
+    Loot,
+    #[allow(dead_code)] //levelupの選択肢,まだ無い
+    Upgrade,
+    #[allow(dead_code)] //critical,まだ無い
+    Crit,
+}
+const STREAM_COUNT: usize = 5;
+
+// 1runの乱数,全てrun seedから作る.replayで同じ値になるようにChaCha8
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    streams: [ChaCha8Rng; STREAM_COUNT],
+}
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+        let stream =
+            |i: u64| ChaCha8Rng::seed_from_u64(seed ^ i.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        Self {
+            seed,
+            streams: [stream(1), stream(2), stream(3), stream(4), stream(5)],
+        }
+    }
+    pub fn from_entropy() -> Self {
+        Self::from_seed(rand::thread_rng().gen())
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn stream(&mut self, stream: RngStream) -> &mut ChaCha8Rng {
+        &mut self.streams[stream as usize]
+    }
+}
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+// titleで入力したseed,無ければランダム
+#[derive(Resource, Default)]
+pub struct RunSeed(pub Option<u64>);
+
+// run開始時にseedから乱数を作り直す
+pub fn setup_game_rng_system(mut rng: ResMut<GameRng>, run_seed: Res<RunSeed>) {
+    *rng = match run_seed.0 {
+        Some(seed) => GameRng::from_seed(seed),
+        None => GameRng::from_entropy(),
+    };
+    info!("run seed:{}", rng.seed());
+}
@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+
+use crate::{
+    game_rng::GameRng,
+    inputmng::{InputMngBtn, MENU_CONFIRM},
+    AppState,
+};
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+#[derive(Resource)]
+pub struct UIGameOverData {
+    button_entity: Entity,
+}
+
+pub fn setup_gameover(mut commands: Commands, game_rng: Res<GameRng>) {
+    let button_entity = commands
+        .spawn(NodeBundle {
+            style: Style {
+                // center button
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(16.),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "GAME OVER",
+                TextStyle {
+                    font_size: 48.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+            // 同じseedで遊べるように表示
+            parent.spawn(TextBundle::from_section(
+                format!("SEED: {}", game_rng.seed()),
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(150.),
+                        height: Val::Px(65.),
+                        // horizontally center child text
+                        justify_content: JustifyContent::Center,
+                        // vertically center child text
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Title",
+                        TextStyle {
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    ));
+                });
+        })
+        .id();
+    commands.insert_resource(UIGameOverData { button_entity });
+}
+
+pub fn gameover_system(
+    input: Res<ButtonInput<InputMngBtn>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    // ボタンが無くても決定で進む
+    if input.just_pressed(MENU_CONFIRM) {
+        next_state.set(AppState::Title);
+    }
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                next_state.set(AppState::Title);
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+pub fn cleanup_gameover(mut commands: Commands, menu_data: Res<UIGameOverData>) {
+    commands.entity(menu_data.button_entity).despawn_recursive();
+}
@@ -0,0 +1,256 @@
+use crate::{
+    bot::{BotControl, BotParams, BotTarget},
+    broadphase::BroadphaseKind,
+    components::{CollideCircle, CollisionLayer, PhysicalObj},
+    fixed_step::SimulationConfig,
+    game_data::{GameDataHandles, GameDataPlugin},
+    game_rng::RunSeed,
+    physics::{PhysicsResource, SHM},
+    physics_config::{PhysicsConfig, PhysicsConfigFile},
+    replay,
+    stats::{RunStats, WaveStats},
+    ui::MenuPlugin,
+    wave::{GameSequence, WaveStatus},
+    AppState, GameConfig, GamePlayPlugin,
+};
+use bevy::{
+    asset::AssetPlugin,
+    prelude::*,
+    tasks::{ComputeTaskPool, TaskPool},
+    time::TimeUpdateStrategy,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const DATA_LOAD_TIMEOUT_SECONDS: u64 = 10;
+
+// --headless --runs <n> --out <path> [--seed <n>] [--waves <n>] [--target nearest|weakest]
+// [--broadphase sparse|dense]
+struct HeadlessArgs {
+    runs: u32,
+    out: PathBuf,
+    seed: u64,
+    max_waves: u32,
+    target: BotTarget,
+    broadphase: BroadphaseKind,
+}
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+}
+
+impl HeadlessArgs {
+    fn from_args(args: &[String]) -> Self {
+        let value = |name: &str| arg_value(args, name);
+        Self {
+            runs: value("--runs").and_then(|v| v.parse().ok()).unwrap_or(1),
+            out: value("--out")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("stats.csv")),
+            seed: value("--seed").and_then(|v| v.parse().ok()).unwrap_or(0),
+            max_waves: value("--waves").and_then(|v| v.parse().ok()).unwrap_or(10),
+            target: match value("--target").map(String::as_str) {
+                Some("weakest") => BotTarget::Weakest,
+                _ => BotTarget::Nearest,
+            },
+            broadphase: match value("--broadphase").map(String::as_str) {
+                Some("dense") => BroadphaseKind::Dense,
+                _ => BroadphaseKind::Sparse,
+            },
+        }
+    }
+}
+
+// window無しでN run回して,waveごとの集計をcsvに書く
+pub fn run(args: &[String]) {
+    if let Some(count) = arg_value(args, "--bench-collision").and_then(|v| v.parse().ok()) {
+        let ticks = arg_value(args, "--ticks")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+        bench_collision(count, ticks);
+        return;
+    }
+    let args = HeadlessArgs::from_args(args);
+    let mut rows = Vec::new();
+    for run in 0..args.runs {
+        let seed = args.seed.wrapping_add(run as u64);
+        let waves = simulate_run(&args, seed);
+        println!("run {run} seed:{seed} waves:{}", waves.len());
+        rows.extend(waves.into_iter().map(|w| (run, seed, w)));
+    }
+    match write_csv(&args.out, &rows) {
+        Ok(()) => println!("stats saved:{}", args.out.display()),
+        Err(e) => eprintln!("stats {}: {e}", args.out.display()),
+    }
+}
+
+/// Gameplay without window, each `update` advances one simulation tick.
+/// The data files are loaded before it returns, in `Title` after `Startup`.
+/// Menus have no UI, press `MENU_CONFIRM` in `ButtonInput<InputMngBtn>` to leave them
+pub fn gameplay_app(seed: u64) -> App {
+    let tick = Duration::from_secs_f64(1. / SimulationConfig::default().tick_hz);
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(AssetPlugin {
+            watch_for_changes_override: Some(false),
+            ..default()
+        })
+        // 1updateで1tick進める
+        .insert_resource(TimeUpdateStrategy::ManualDuration(tick))
+        .add_plugins((GamePlayPlugin, GameDataPlugin, MenuPlugin))
+        .insert_resource(RunSeed(Some(seed)))
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn(GameConfig { ..default() });
+        });
+    app.finish();
+    app.cleanup();
+    load_data_files(&mut app);
+    app
+}
+
+// level,武器,物理のファイルを読み終わるまで回す.読めなければ既定の値のまま
+fn load_data_files(app: &mut App) {
+    let start = Instant::now();
+    loop {
+        app.update();
+        let world = &app.world;
+        let asset_server = world.resource::<AssetServer>();
+        if world
+            .resource::<GameDataHandles>()
+            .is_finished(asset_server)
+            && world
+                .resource::<PhysicsConfigFile>()
+                .is_finished(asset_server)
+        {
+            break;
+        }
+        if start.elapsed() > Duration::from_secs(DATA_LOAD_TIMEOUT_SECONDS) {
+            warn!("data files are not loaded");
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    app.update(); //読めたassetを反映
+}
+
+fn build_app(args: &HeadlessArgs, seed: u64) -> App {
+    let mut app = gameplay_app(seed);
+    app.add_systems(Update, replay::auto_advance_menu_system)
+        .insert_resource(BotControl::enabled())
+        .insert_resource(BotParams {
+            target: args.target,
+            ..default()
+        })
+        .insert_resource(PhysicsResource {
+            broadphase: args.broadphase,
+            ..default()
+        });
+    app
+}
+
+fn simulate_run(args: &HeadlessArgs, seed: u64) -> Vec<WaveStats> {
+    let max_waves = args.max_waves;
+    let mut app = build_app(args, seed);
+    let tick_hz = SimulationConfig::default().tick_hz;
+    // 最後のwaveが終わらない時の保険
+    let wave_seconds = WaveStatus::default().timer.duration().as_secs_f64();
+    let max_ticks = ((max_waves as f64 + 1.) * wave_seconds * tick_hz) as u64;
+    for _ in 0..max_ticks {
+        app.update();
+        let state = *app.world.resource::<State<AppState>>().get();
+        let wave_no = app.world.resource::<GameSequence>().wave_no;
+        if state == AppState::GameOver || (state == AppState::LevelUp && wave_no + 1 >= max_waves) {
+            break;
+        }
+    }
+    std::mem::take(&mut app.world.resource_mut::<RunStats>().waves)
+}
+
+fn write_csv(path: &Path, rows: &[(u32, u64, WaveStats)]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "run,seed,wave,kills,damage_taken,time_alive")?;
+    for (run, seed, w) in rows {
+        writeln!(
+            file,
+            "{run},{seed},{},{},{},{:.3}",
+            w.wave_no, w.kills, w.damage_taken, w.time_alive
+        )?;
+    }
+    file.flush()
+}
+
+// 衝突解決だけの世界,count個の円を敷き詰める
+fn collision_world(count: usize, kind: BroadphaseKind, parallel: bool) -> (World, Schedule) {
+    let mut world = World::new();
+    world.insert_resource(SHM::new(kind));
+    world.init_resource::<PhysicsConfig>();
+    world.insert_resource(PhysicsResource {
+        parallel_collision: parallel,
+        broadphase: kind,
+        ..default()
+    });
+    let mut rng = StdRng::seed_from_u64(0);
+    let half = (count as f32).sqrt() * 4.; //1個あたり8x8くらい
+    for _ in 0..count {
+        let pos = Vec2::new(rng.gen_range(-half..half), rng.gen_range(-half..half));
+        world.spawn((
+            Transform::from_translation(pos.extend(0.)),
+            CollideCircle::new(CollisionLayer::Enemy),
+            PhysicalObj {
+                old_pos: pos,
+                ..default()
+            },
+        ));
+    }
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+        (
+            crate::physics::shm_pre_proc_system,
+            crate::solver::solve_contacts_system,
+        )
+            .chain(),
+    );
+    (world, schedule)
+}
+
+// --headless --bench-collision <n> [--ticks <n>]
+// serial版と並列版の衝突解決の時間,結果の差
+fn bench_collision(count: usize, ticks: u32) {
+    ComputeTaskPool::get_or_init(TaskPool::default);
+    let mut results = Vec::new();
+    let modes = [BroadphaseKind::Sparse, BroadphaseKind::Dense]
+        .into_iter()
+        .flat_map(|kind| [(kind, false), (kind, true)]);
+    for (kind, parallel) in modes {
+        let (mut world, mut schedule) = collision_world(count, kind, parallel);
+        schedule.run(&mut world);
+        let mut query = world.query::<&PhysicalObj>();
+        results.push(
+            query
+                .iter(&world)
+                .map(|obj| obj.move_vec)
+                .collect::<Vec<_>>(),
+        );
+        let start = Instant::now();
+        for _ in 0..ticks {
+            schedule.run(&mut world);
+        }
+        let ms = start.elapsed().as_secs_f64() * 1000. / ticks as f64;
+        let name = if parallel { "parallel" } else { "serial" };
+        println!("collision {kind:?} {name}: {count} bodies {ms:.3}ms/tick");
+    }
+    // 全部同じ結果になるはず
+    let max_diff = results[1..]
+        .iter()
+        .flat_map(|r| r.iter().zip(&results[0]).map(|(a, b)| a.distance(*b)))
+        .fold(0., f32::max);
+    println!("max move_vec diff:{max_diff}");
+}
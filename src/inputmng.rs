@@ -1,5 +1,6 @@
 //use bevy::crate::bevy_input::button_input::ButtonInput;
-use bevy::prelude::*;
+use crate::components::MainCamera;
+use bevy::{prelude::*, window::PrimaryWindow};
 
 #[derive(TypePath, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum InputMngBtn {
@@ -11,9 +12,65 @@ pub enum InputMngBtn {
     Dash,
     Melee,
 }
+impl InputMngBtn {
+    pub const ALL: [InputMngBtn; 7] = [
+        InputMngBtn::Up,
+        InputMngBtn::Down,
+        InputMngBtn::Left,
+        InputMngBtn::Right,
+        InputMngBtn::Shot,
+        InputMngBtn::Dash,
+        InputMngBtn::Melee,
+    ];
+}
+
+// menuの決定ボタン
+pub const MENU_CONFIRM: InputMngBtn = InputMngBtn::Shot;
+
+// 照準,cursorのworld座標
+#[derive(Resource, Default)]
+pub struct AimInput {
+    pub pos: Option<Vec2>,
+}
 
 pub fn startup_input_mng_system(mut commands: Commands) {
     commands.init_resource::<ButtonInput<InputMngBtn>>();
+    commands.init_resource::<AimInput>();
+}
+
+// frameの終わりにjust_pressedを消す,押し続けても1回だけ
+pub fn clear_input_mng_system(mut input: ResMut<ButtonInput<InputMngBtn>>) {
+    input.clear();
+}
+
+fn calc_screen_to_world_position(
+    screen_pos: Vec2,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Vec2 {
+    let world_pos = camera
+        .viewport_to_world(camera_transform, screen_pos)
+        .map(|ray| ray.origin.truncate());
+    match world_pos {
+        Some(p) => p,
+        None => Vec2::ZERO,
+    }
+}
+
+pub fn update_aim_input_system(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut aim: ResMut<AimInput>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    aim.pos = window
+        .cursor_position()
+        .map(|cur_pos| calc_screen_to_world_position(cur_pos, camera, camera_transform));
 }
 
 pub fn update_input_mng_system(
@@ -0,0 +1,214 @@
+use crate::components::*;
+use bevy::prelude::*;
+use bot::{AttractModePlugin, BotPlugin};
+use combat::CombatPlugin;
+use debug::DebugPlugin;
+use enemy::EnemyPlugin;
+use fixed_step::FixedStepPlugin;
+use game_data::GameDataPlugin;
+use game_rng::{GameRng, RunSeed};
+use moonshine_save::prelude::*;
+use physics::PhysicsPlugin;
+use pickup::PickupPlugin;
+use player::PlayerPlugin;
+use pool::EntityPool;
+use replay::ReplayPlugin;
+use sprite_atlas::SpriteAtlasPlugin;
+use stats::StatsPlugin;
+use std::path::Path;
+use ui::UiPlugin;
+use wave::WavePlugin;
+
+mod bot;
+pub mod broadphase;
+mod camera;
+pub mod combat;
+pub mod components;
+mod data_asset;
+pub mod debug;
+pub mod dense_grid;
+mod dw_gui;
+pub mod enemy;
+mod enemy_behavior;
+mod fixed_step;
+mod game_data;
+mod game_rng;
+mod gameover;
+pub mod headless;
+pub mod inputmng;
+mod levelup;
+pub mod physics;
+pub mod physics_config;
+mod pickup;
+mod player;
+mod pool;
+mod replay;
+mod resources;
+pub mod shape;
+mod shop;
+mod show_debug;
+mod show_fps;
+pub mod solver;
+pub mod sparse_grid;
+mod sprite_atlas;
+pub mod stats;
+mod title;
+pub mod ui;
+mod ui_game;
+pub mod wave;
+
+const SAVE_CONFIG_PATH: &str = "ram/config.ron";
+
+#[derive(Resource)]
+pub struct GameFonts {
+    cmn: Handle<Font>,
+}
+
+#[derive(Component, Debug, Reflect)]
+#[reflect(Component)]
+pub struct GameConfig {
+    dbg_show_collision: bool,
+    dbg_least_time: bool, //ゲームすぐに終了
+    float: f32,
+}
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            dbg_show_collision: false,
+            dbg_least_time: false,
+            float: 0.,
+        }
+    }
+}
+#[derive(Bundle)]
+struct GameConfigBundle {
+    game_config: GameConfig,
+    save: Save,
+}
+
+/// A resource which is used to invoke the save system.
+#[derive(Resource)]
+struct SaveConfigRequest;
+impl SaveIntoFileRequest for SaveConfigRequest {
+    fn path(&self) -> &Path {
+        SAVE_CONFIG_PATH.as_ref()
+    }
+}
+
+/// A resource which is used to invoke the load system.
+#[derive(Resource)]
+struct LoadConfigRequest;
+impl LoadFromFileRequest for LoadConfigRequest {
+    fn path(&self) -> &Path {
+        SAVE_CONFIG_PATH.as_ref()
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States, serde::Serialize, serde::Deserialize,
+)]
+pub enum AppState {
+    #[default]
+    Title,
+    InGame,
+    LevelUp,
+    Shop,
+    GameOver,
+}
+
+/// The whole game for a window, add it after `DefaultPlugins`
+#[derive(Default)]
+pub struct GamePlugin {
+    replay: ReplayPlugin,
+}
+impl GamePlugin {
+    // --record <path> / --replay <path>
+    pub fn from_args(args: &[String]) -> Self {
+        Self {
+            replay: ReplayPlugin::from_args(args),
+        }
+    }
+}
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((GameDataPlugin, SpriteAtlasPlugin))
+            //save load
+            .add_plugins(SavePlugin)
+            .register_type::<GameConfig>()
+            .add_systems(
+                PreUpdate,
+                //save_default().into_file_on_request::<SaveConfigRequest>(),
+                save::<With<GameConfig>>().into_file_on_request::<SaveConfigRequest>(),
+            )
+            .add_systems(PreUpdate, load_from_file_on_request::<LoadConfigRequest>())
+            .add_plugins((GamePlayPlugin, UiPlugin, DebugPlugin))
+            .add_plugins(self.replay.clone())
+            .add_plugins(AttractModePlugin)
+            .add_systems(PreStartup, pre_startup_setup_system)
+            .add_systems(Update, bevy::window::close_on_esc)
+            .add_systems(
+                PreUpdate,
+                (
+                    inputmng::update_input_mng_system,
+                    inputmng::update_aim_input_system,
+                )
+                    .after(bevy::input::InputSystem)
+                    .run_if(replay::is_live_input),
+            );
+    }
+}
+
+/// Gameplay, physics and waves, runs without window
+pub struct GamePlayPlugin;
+impl Plugin for GamePlayPlugin {
+    fn build(&self, app: &mut App) {
+        // gameplay,physicsはFixedUpdateで回す
+        app.configure_sets(
+            FixedUpdate,
+            (
+                GameSystemSet::Update.after(GameSystemSet::PreProcess),
+                GameSystemSet::UpdatePhysics.after(GameSystemSet::Update),
+                GameSystemSet::PostPhysics.after(GameSystemSet::UpdatePhysics),
+                GameSystemSet::PostUpdate.after(GameSystemSet::PostPhysics),
+            ),
+        )
+        .init_resource::<EntityPool>()
+        .init_resource::<GameRng>()
+        .init_resource::<RunSeed>()
+        .add_plugins((FixedStepPlugin, StatsPlugin))
+        .add_systems(Startup, inputmng::startup_input_mng_system)
+        .add_systems(Last, inputmng::clear_input_mng_system)
+        .init_state::<AppState>()
+        .add_systems(OnExit(AppState::Title), game_rng::setup_game_rng_system)
+        // GameOver,demo終了でtitleに戻った時
+        .add_systems(OnEnter(AppState::Title), player::reset_player_state_system)
+        .add_plugins((PhysicsPlugin, CombatPlugin, WavePlugin))
+        //InGame
+        .add_plugins((PlayerPlugin, EnemyPlugin, PickupPlugin, BotPlugin));
+    }
+}
+
+fn pre_startup_setup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // camera
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                hdr: true, // 1. HDR is required for bloom
+                ..default()
+            },
+            ..default()
+        },
+        MainCamera,
+    ));
+    // add font resource
+    let game_fonts = GameFonts {
+        cmn: asset_server.load("MPLUS1Code-Regular.ttf"),
+    };
+    commands.insert_resource(game_fonts);
+    commands.spawn((GameConfigBundle {
+        game_config: GameConfig { ..default() },
+        save: Save,
+    },));
+    commands.insert_resource(crate::LoadConfigRequest);
+}
@@ -0,0 +1,266 @@
+use crate::{
+    broadphase::{Broadphase2d, BroadphaseKind},
+    components::*,
+    physics_config::{PhysicsConfig, PhysicsConfigPlugin},
+    pool::Inactive,
+    solver, AppState,
+};
+use bevy::prelude::*;
+
+const TILE_SIZE: usize = 10;
+
+// 移動,broadphase,押し合い,verlet積分
+pub struct PhysicsPlugin;
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PhysicsResource { ..default() })
+            .insert_resource(SHM::new(BroadphaseKind::default()))
+            .add_plugins(PhysicsConfigPlugin)
+            .add_systems(
+                FixedUpdate,
+                (physical_obj_pre_proc_system, shm_pre_proc_system)
+                    .in_set(GameSystemSet::PreProcess)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                uniform_linear_motion_system
+                    .in_set(GameSystemSet::Update)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                solver::solve_contacts_system
+                    .in_set(GameSystemSet::UpdatePhysics)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                physical_obj_do_verlet_system
+                    .in_set(GameSystemSet::PostPhysics)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct PhysicsResource {
+    pub prev_dt: f32,             //1frame前のdt
+    pub parallel_collision: bool, //falseならserialで衝突解決,比較用
+    pub broadphase: BroadphaseKind,
+}
+impl Default for PhysicsResource {
+    fn default() -> Self {
+        Self {
+            prev_dt: 1.0 / 60.0,
+            parallel_collision: true,
+            broadphase: BroadphaseKind::default(),
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct SHM {
+    kind: BroadphaseKind,
+    pub(crate) sg2: Box<dyn Broadphase2d>,
+}
+impl SHM {
+    pub fn new(kind: BroadphaseKind) -> Self {
+        Self {
+            kind,
+            sg2: kind.create::<TILE_SIZE>(),
+        }
+    }
+}
+
+pub fn physical_obj_pre_proc_system(
+    mut query: Query<(&Transform, &mut PhysicalObj), Without<Inactive>>,
+) {
+    for (transform, mut obj) in query.iter_mut() {
+        obj.move_vec = Vec2::ZERO;
+        obj.old_move_vec = Vec2::ZERO;
+        obj.force = Vec2::ZERO;
+        obj.velocity = transform.translation.xy() - obj.old_pos;
+        obj.collision_count = 0;
+    }
+}
+
+pub fn shm_pre_proc_system(
+    mut commands: Commands,
+    mut shm: ResMut<SHM>,
+    physics_resource: Res<PhysicsResource>,
+    mut query: Query<
+        (Entity, &Transform, &CollideCircle, Option<&mut GridAabb>),
+        Without<Inactive>,
+    >,
+    inactive_query: Query<Entity, (With<GridAabb>, With<Inactive>)>,
+    mut removed: RemovedComponents<GridAabb>,
+    pl_query: Query<&Transform, With<Player>>,
+) {
+    // broadphaseの切り替え,全部登録しなおす
+    let rebuild = shm.kind != physics_resource.broadphase;
+    if rebuild {
+        *shm = SHM::new(physics_resource.broadphase);
+    }
+    // despawn,poolに戻ったものを消す
+    for entity in removed.read() {
+        shm.sg2.remove(entity);
+    }
+    for entity in inactive_query.iter() {
+        shm.sg2.remove(entity);
+        commands.entity(entity).remove::<GridAabb>();
+    }
+    if let Ok(tf) = pl_query.get_single() {
+        shm.sg2.recenter(tf.translation.xy());
+    }
+    // cellが変わったものだけ更新
+    for (entity, transform, colli, grid_aabb) in query.iter_mut() {
+        let aabb = colli.collider(transform).aabb();
+        match grid_aabb {
+            Some(mut grid_aabb) if !rebuild => {
+                if grid_aabb.0 != aabb {
+                    shm.sg2.update(entity, grid_aabb.0, aabb);
+                    grid_aabb.0 = aabb;
+                }
+            }
+            Some(mut grid_aabb) => {
+                shm.sg2.insert_aabb(aabb, entity);
+                grid_aabb.0 = aabb;
+            }
+            None => {
+                shm.sg2.insert_aabb(aabb, entity);
+                commands.entity(entity).insert(GridAabb(aabb));
+            }
+        }
+    }
+    shm.sg2.finish();
+}
+
+pub fn physical_obj_do_verlet_system(
+    time: Res<Time>,
+    mut physics_resource: ResMut<PhysicsResource>,
+    config: Res<PhysicsConfig>,
+    mut query: Query<(Entity, &mut PhysicalObj, &mut Transform), Without<Inactive>>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0. {
+        return;
+    };
+    let inv_prev_dt = 1. / physics_resource.prev_dt;
+    let decel = f32::powf(config.damping, dt);
+    for (_entity, mut obj, mut transform) in query.iter_mut() {
+        let decel = obj.damping.map_or(decel, |damping| f32::powf(damping, dt));
+        let mov_vec = obj.move_vec;
+        let pos = transform.translation.xy() + mov_vec;
+        let mut tmp = obj.old_pos + mov_vec;
+        tmp = tmp + obj.old_move_vec; //change velocity
+
+        // do verlet
+        let vel = (pos - tmp) * inv_prev_dt;
+        let inv_mass_dt = obj.inv_mass * dt;
+        let vel = vel + obj.force * inv_mass_dt;
+        let vel = vel * decel; //damping
+
+        // 押し合っている間は速さを抑える,接触が多いほど遅く
+        let vel = if obj.collision_count >= 2 && config.crowd_max_speed > 0. {
+            vel.clamp_length_max(config.crowd_max_speed / obj.collision_count as f32)
+        } else {
+            vel
+        };
+
+        let tmp = pos + vel * dt;
+
+        // set_position
+        let translation = &mut transform.translation;
+        *translation = tmp.extend(translation.z);
+        obj.old_pos = pos;
+        // set_velocity
+        obj.velocity = vel;
+    }
+    physics_resource.prev_dt = dt;
+}
+
+// 等速直線運動,bullet等
+fn uniform_linear_motion_system(
+    time: Res<Time>,
+    mut query: Query<(&UniformVelocity, &mut PhysicalObj), Without<Inactive>>,
+) {
+    for (v, mut obj) in query.iter_mut() {
+        obj.move_vec = v.0 * time.delta_seconds();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        enemy::EnemyCount,
+        headless::gameplay_app,
+        pool::{EntityPool, PoolKind, Pooled},
+        sparse_grid::Aabb,
+    };
+    use bevy::{ecs::system::RunSystemOnce, time::TimeUpdateStrategy};
+    use std::time::Duration;
+
+    fn in_grid(app: &App, entity: Entity, pos: Vec2) -> bool {
+        let mut out = Vec::new();
+        let shm = app.world.resource::<SHM>();
+        shm.sg2
+            .query_aabb_into(Aabb::from_circle(pos, 1.), &mut out);
+        out.contains(&entity)
+    }
+
+    // poolに戻した次のtickで使い直す,1frameで2tick回ってもbroadphaseに残る
+    #[test]
+    fn reused_entity_stays_in_grid() {
+        let mut app = gameplay_app(0);
+        app.world.resource_mut::<EnemyCount>().max = 0;
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        app.update();
+        let body = |pos: Vec2| {
+            (
+                Transform::from_translation(pos.extend(0.)),
+                PhysicalObj {
+                    old_pos: pos,
+                    ..default()
+                },
+                CollideCircle::new(CollisionLayer::Enemy),
+                Pooled(PoolKind::Enemy),
+            )
+        };
+        let (pos, gone_pos) = (Vec2::new(100., 0.), Vec2::new(-100., 0.));
+        let entity = app.world.spawn(body(pos)).id();
+        let gone = app.world.spawn(body(gone_pos)).id();
+        app.update();
+        assert!(in_grid(&app, entity, pos) && in_grid(&app, gone, gone_pos));
+
+        // poolに戻したものをgridから外したtickで取り出す
+        app.add_systems(
+            FixedUpdate,
+            (|mut commands: Commands,
+              mut pool: ResMut<EntityPool>,
+              query: Query<(), (With<Inactive>, Without<GridAabb>)>| {
+                if !query.is_empty() {
+                    pool.spawn(&mut commands, PoolKind::Enemy, ());
+                }
+            })
+            .in_set(GameSystemSet::PostUpdate),
+        );
+        app.world.run_system_once(
+            move |mut commands: Commands, mut pool: ResMut<EntityPool>| {
+                pool.release(&mut commands, entity, PoolKind::Enemy);
+                commands.entity(gone).despawn();
+            },
+        );
+        let tick = Duration::from_secs_f64(1. / 60.);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(tick * 2));
+        app.update();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(tick));
+        app.update();
+        assert!(app.world.get::<Inactive>(entity).is_none());
+        assert!(in_grid(&app, entity, pos));
+        assert!(!in_grid(&app, gone, gone_pos));
+    }
+}
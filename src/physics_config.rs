@@ -0,0 +1,153 @@
+use crate::data_asset::{changed, is_load_finished, DataAsset, DataAssetPlugin, ASSET_DIR};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const PHYSICS_CONFIG_PATH: &str = "game.physics.ron";
+
+/// Physics parameters, `PhysicalObj` can override some of them per body
+#[derive(Resource, Asset, TypePath, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    /// Velocity kept after one second
+    pub damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    /// Contact solver iterations per tick
+    pub solver_iterations: u32,
+    /// Scale of each solver step, above 1 converges faster
+    pub solver_relaxation: f32,
+    /// Speed limit while touching two or more bodies, divided by the contact count (0 = off)
+    pub crowd_max_speed: f32,
+}
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            damping: 0.4,
+            restitution: 0.5,
+            friction: 0.2,
+            solver_iterations: 4,
+            solver_relaxation: 1.5,
+            crowd_max_speed: 240.,
+        }
+    }
+}
+
+impl DataAsset for PhysicsConfig {
+    fn validate(&self, errors: &mut Vec<String>) {
+        let mut check = |ok: bool, message: &str| {
+            if !ok {
+                errors.push(message.into());
+            }
+        };
+        check(
+            (0.0..=1.0).contains(&self.damping),
+            "damping must be 0 to 1",
+        );
+        check(
+            (0.0..=1.0).contains(&self.restitution),
+            "restitution must be 0 to 1",
+        );
+        check(self.friction >= 0., "friction must not be negative");
+        check(
+            self.solver_iterations > 0,
+            "solver_iterations must be positive",
+        );
+        check(
+            self.solver_relaxation > 0.,
+            "solver_relaxation must be positive",
+        );
+        check(
+            self.crowd_max_speed >= 0.,
+            "crowd_max_speed must not be negative",
+        );
+    }
+}
+
+impl PhysicsConfig {
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let text =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(std::io::Error::other)?;
+        std::fs::write(path, text)
+    }
+}
+
+/// The physics config file, applied to [`PhysicsConfig`] when it is loaded or modified
+#[derive(Resource, Debug)]
+pub struct PhysicsConfigFile {
+    pub handle: Handle<PhysicsConfig>,
+}
+impl PhysicsConfigFile {
+    /// Whether the file finished loading, a failed file keeps the current values
+    pub fn is_finished(&self, asset_server: &AssetServer) -> bool {
+        is_load_finished(asset_server, &self.handle)
+    }
+
+    /// Reads the file again, even if it did not change
+    pub fn reload(&self, asset_server: &AssetServer) {
+        asset_server.reload(PHYSICS_CONFIG_PATH);
+    }
+
+    /// Writes `config` to the file, the file watcher loads it back
+    pub fn save(&self, config: &PhysicsConfig) -> std::io::Result<()> {
+        config.save(&Path::new(ASSET_DIR).join(PHYSICS_CONFIG_PATH))
+    }
+}
+
+pub struct PhysicsConfigPlugin;
+impl Plugin for PhysicsConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DataAssetPlugin::<PhysicsConfig>::new(&[
+            "physics.ron",
+            "physics.json",
+            "physics.toml",
+        ]))
+        .init_resource::<PhysicsConfig>()
+        .add_systems(PreStartup, load_physics_config_system)
+        .add_systems(Update, apply_physics_config_system);
+    }
+}
+
+fn load_physics_config_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PhysicsConfigFile {
+        handle: asset_server.load(PHYSICS_CONFIG_PATH),
+    });
+}
+
+// 読めたら,更新されたら反映する.読めなければ今の値のまま
+fn apply_physics_config_system(
+    file: Res<PhysicsConfigFile>,
+    mut events: EventReader<AssetEvent<PhysicsConfig>>,
+    assets: Res<Assets<PhysicsConfig>>,
+    mut config: ResMut<PhysicsConfig>,
+) {
+    if !changed(&mut events, &file.handle) {
+        return;
+    }
+    if let Some(loaded) = assets.get(&file.handle) {
+        info!("physics config loaded:{PHYSICS_CONFIG_PATH}");
+        *config = loaded.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_asset::load_asset_file;
+
+    #[test]
+    fn asset_is_default() {
+        let config: PhysicsConfig = load_asset_file(PHYSICS_CONFIG_PATH);
+        assert_eq!(config, PhysicsConfig::default());
+        let mut errors = Vec::new();
+        config.validate(&mut errors);
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn missing_fields_use_default() {
+        let config: PhysicsConfig = ron::from_str("(damping: 0.9)").unwrap();
+        assert_eq!(config.damping, 0.9);
+        assert_eq!(config.solver_iterations, 4);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    components::*,
+    pool::{EntityPool, Inactive, PoolKind},
+    shape,
+    wave::GameSequence,
+    AppState,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use std::time::Duration;
+
+const PICKUP_RADIUS: f32 = 7.; //playerの円(3)と合わせて10で拾う
+const DROP_RATE: f64 = 0.7; //敵を倒した時に落とす確率
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            pickup_collect_system
+                .in_set(GameSystemSet::PostUpdate)
+                .before(crate::combat::update_entity_existence_system)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+// 確率で落とす
+pub fn drop_pickup(commands: &mut Commands, pool: &mut EntityPool, rng: &mut impl Rng, pos: Vec2) {
+    if rng.gen_bool(DROP_RATE) {
+        spawn_pickup(commands, pool, pos);
+    }
+}
+
+pub fn spawn_pickup(commands: &mut Commands, pool: &mut EntityPool, pos: Vec2) -> Entity {
+    pool.spawn(
+        commands,
+        PoolKind::Pickup,
+        (
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgb(0.4, 0.9, 0.5),
+                    custom_size: Some(Vec2::new(3., 3.)),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: pos.extend(4.),
+                    ..default()
+                },
+                ..default()
+            },
+            Pickup { exp: 1 },
+            HitCircle {
+                radius: PICKUP_RADIUS,
+                ..HitCircle::new(CollisionLayer::Pickup)
+            },
+            Lifetime(Timer::from_seconds(30., TimerMode::Once)),
+        ),
+    )
+}
+
+// 拾ったら寿命を0にして,update_entity_existence_systemでpoolに戻す
+// pickupの層がplayerに当たり判定する時だけ
+fn pickup_collect_system(
+    mut game_sequence: ResMut<GameSequence>,
+    q_player: Query<(&Transform, &CollideCircle), With<Player>>,
+    mut q_pickup: Query<(&Transform, &HitCircle, &mut Pickup, &mut Lifetime), Without<Inactive>>,
+) {
+    let Ok((pl_tf, pl_colli)) = q_player.get_single() else {
+        return;
+    };
+    let pl_collider = pl_colli.collider(pl_tf);
+    for (tf, hit, mut pickup, mut lifetime) in q_pickup.iter_mut() {
+        if pickup.exp > 0
+            && hit.filter.overlaps(&pl_colli.filter)
+            && shape::contact(&hit.collider(tf), &pl_collider).is_some()
+        {
+            game_sequence.exp += pickup.exp;
+            pickup.exp = 0;
+            lifetime.0.set_duration(Duration::ZERO);
+        }
+    }
+}
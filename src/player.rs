@@ -1,8 +1,14 @@
-use crate::{components::*, inputmng::InputMngBtn, AppState};
-use bevy::{prelude::*, window::PrimaryWindow};
+use crate::{
+    components::*,
+    inputmng::{AimInput, InputMngBtn},
+    pool::{EntityPool, PoolKind},
+    shape::Shape,
+    AppState,
+};
+use bevy::prelude::*;
 
 #[derive(Resource)]
-struct PlayerState {
+pub struct PlayerState {
     alive: bool, // alive
 }
 impl Default for PlayerState {
@@ -15,8 +21,9 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(PlayerState::default())
+            .init_resource::<WeaponParams>()
             .add_systems(
-                Update,
+                FixedUpdate,
                 (
                     player_input_move_event_system,
                     player_input_shot_event_system,
@@ -26,7 +33,7 @@ impl Plugin for PlayerPlugin {
                     .run_if(in_state(AppState::InGame)),
             )
             .add_systems(
-                Update,
+                FixedUpdate,
                 player_spawn_system
                     .in_set(GameSystemSet::PostUpdate)
                     .run_if(in_state(AppState::InGame)),
@@ -34,7 +41,11 @@ impl Plugin for PlayerPlugin {
     }
 }
 
-fn player_spawn_system(mut commands: Commands, mut player_state: ResMut<PlayerState>) {
+fn player_spawn_system(
+    mut commands: Commands,
+    mut player_state: ResMut<PlayerState>,
+    weapon_params: Res<WeaponParams>,
+) {
     if !player_state.alive {
         let player_pos = Vec2::new(0., 0.);
         commands
@@ -57,17 +68,23 @@ fn player_spawn_system(mut commands: Commands, mut player_state: ResMut<PlayerSt
             })
             .insert(CollideCircle {
                 radius: 3.,
-                ..default()
+                ..CollideCircle::new(CollisionLayer::Player)
             })
             .insert(Health::from_max(10.))
+            .insert(Invincible(Timer::from_seconds(1., TimerMode::Once)))
             .with_children(|parent| {
-                parent.spawn(Weapon { ..default() }).insert(ForPlayer);
+                parent.spawn(Weapon::new(&weapon_params)).insert(ForPlayer);
             });
 
         player_state.alive = true; //spawned
     }
 }
 
+// 新しいrunでplayerを出し直す
+pub fn reset_player_state_system(mut player_state: ResMut<PlayerState>) {
+    *player_state = PlayerState::default();
+}
+
 fn player_input_move_event_system(
     input: Res<ButtonInput<InputMngBtn>>,
     time: Res<Time>,
@@ -97,84 +114,74 @@ fn player_input_move_event_system(
     obj.move_vec += mov * time.delta_seconds() * 60.;
 }
 
-fn calc_screen_to_world_position(
-    screen_pos: Vec2,
-    camera: &Camera,
-    camera_transform: &GlobalTransform,
-) -> Vec2 {
-    let world_pos = camera
-        .viewport_to_world(camera_transform, screen_pos)
-        .map(|ray| ray.origin.truncate());
-    match world_pos {
-        Some(p) => p,
-        None => Vec2::ZERO,
-    }
-}
-
 fn player_input_shot_event_system(
     mut commands: Commands,
     time: Res<Time>,
     input: Res<ButtonInput<InputMngBtn>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    aim: Res<AimInput>,
+    mut pool: ResMut<EntityPool>,
     query: Query<&Transform, With<Player>>,
     mut weapon_query: Query<&mut Weapon, With<ForPlayer>>,
 ) {
     let Ok(tf) = query.get_single() else {
         return;
     };
-    let Ok(window) = window_query.get_single() else {
-        return;
-    };
-    let Ok((camera, camera_transform)) = camera_query.get_single() else {
-        return;
-    };
-    let Some(cur_pos) = window.cursor_position() else {
+    let Some(cur_world_pos) = aim.pos else {
         return;
     };
 
     //shot
     if input.pressed(InputMngBtn::Shot) {
-        let cur_world_pos = calc_screen_to_world_position(cur_pos, camera, camera_transform);
         let pos = tf.translation.xy();
         let Some(dir) = (cur_world_pos - pos).try_normalize() else {
             return;
         };
-        let velocity = dir * 150.;
-
-        let mut spawn_bullet = |offset: Vec2| {
+        let mut spawn_bullet = |offset: Vec2, params: &WeaponParams| {
             let bullet_pos = pos + offset;
-            commands
-                .spawn(SpriteBundle {
-                    sprite: Sprite {
-                        color: Color::rgb(0.95, 0.95, 0.95),
-                        custom_size: Some(Vec2::new(8., 4.)),
-                        ..Default::default()
+            pool.spawn(
+                &mut commands,
+                PoolKind::Bullet,
+                (
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgb(0.95, 0.95, 0.95),
+                            custom_size: Some(Vec2::new(8., 4.)),
+                            ..Default::default()
+                        },
+                        transform: Transform {
+                            translation: bullet_pos.extend(10.),
+                            rotation: Quat::from_rotation_z(dir.y.atan2(dir.x)), //angle
+                            ..Default::default()
+                        },
+                        ..default()
                     },
-                    transform: Transform {
-                        translation: bullet_pos.extend(10.),
-                        rotation: Quat::from_rotation_z(dir.y.atan2(dir.x)), //angle
-                        ..Default::default()
+                    UniformVelocityBundle {
+                        velocity: UniformVelocity(dir * params.bullet_speed),
+                        physicalobj: PhysicalObj {
+                            old_pos: bullet_pos,
+                            ..default()
+                        },
                     },
-                    ..default()
-                })
-                .insert(UniformVelocityBundle {
-                    velocity: UniformVelocity(velocity),
-                    physicalobj: PhysicalObj {
-                        old_pos: bullet_pos,
-                        ..default()
+                    Lifetime(Timer::from_seconds(params.lifetime, TimerMode::Once)),
+                    DamageSource {
+                        damage: params.damage,
                     },
-                    ..default()
-                })
-                .insert(Lifetime(Timer::from_seconds(1., TimerMode::Once)))
-                .insert(DamageSource { ..default() })
-                .insert(HitCircle { ..default() })
-                .insert(FromPlayer);
+                    HitCircle {
+                        radius: 0.,
+                        shape: Shape::OrientedBox {
+                            half_width: 4.,
+                            half_height: 2.,
+                        }, //spriteと同じ大きさ
+                        ..HitCircle::new(CollisionLayer::PlayerShot)
+                    },
+                    FromPlayer,
+                ),
+            );
         };
         for mut weapon in weapon_query.iter_mut() {
             weapon.repeat.tick(time.delta());
             if weapon.repeat.finished() {
-                spawn_bullet(dir * 4.);
+                spawn_bullet(dir * 4., &weapon.params);
             }
         }
     }
@@ -0,0 +1,48 @@
+use bevy::{prelude::*, utils::HashMap};
+
+// 使い回すentityの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolKind {
+    Bullet,
+    Enemy,
+    Pickup,
+}
+
+// poolで管理するentity
+#[derive(Component)]
+pub struct Pooled(pub PoolKind);
+
+// poolに戻っている,gameplayのqueryからは除外する
+#[derive(Component)]
+pub struct Inactive;
+
+#[derive(Resource, Default)]
+pub struct EntityPool {
+    free: HashMap<PoolKind, Vec<Entity>>,
+}
+
+impl EntityPool {
+    // poolから取り出す,無ければspawnする
+    pub fn spawn(
+        &mut self,
+        commands: &mut Commands,
+        kind: PoolKind,
+        bundle: impl Bundle,
+    ) -> Entity {
+        match self.free.get_mut(&kind).and_then(|v| v.pop()) {
+            Some(entity) => {
+                commands.entity(entity).remove::<Inactive>().insert(bundle);
+                entity
+            }
+            None => commands.spawn(bundle).insert(Pooled(kind)).id(),
+        }
+    }
+
+    // poolに戻す,非表示にしてInactiveをつける
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity, kind: PoolKind) {
+        commands
+            .entity(entity)
+            .insert((Inactive, Visibility::Hidden));
+        self.free.entry(kind).or_default().push(entity);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    fixed_step::{RunTick, SimulationConfig},
+    game_rng::{GameRng, RunSeed},
+    inputmng::{AimInput, InputMngBtn},
+    AppState,
+};
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+// 1tick分の入力
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub buttons: u8, //InputMngBtn::ALLの順のbit
+    pub aim: Option<[f32; 2]>,
+}
+impl ReplayFrame {
+    pub fn capture(input: &ButtonInput<InputMngBtn>, aim: &AimInput) -> Self {
+        let mut buttons = 0;
+        for (i, btn) in InputMngBtn::ALL.iter().enumerate() {
+            if input.pressed(*btn) {
+                buttons |= 1 << i;
+            }
+        }
+        Self {
+            buttons,
+            aim: aim.pos.map(|p| p.to_array()),
+        }
+    }
+    pub fn apply(&self, input: &mut ButtonInput<InputMngBtn>, aim: &mut AimInput) {
+        for (i, btn) in InputMngBtn::ALL.iter().enumerate() {
+            if self.buttons & (1 << i) != 0 {
+                input.press(*btn);
+            } else {
+                input.release(*btn);
+            }
+        }
+        aim.pos = self.aim.map(Vec2::from_array);
+    }
+}
+
+// 状態の遷移,RunTickの何tick目か
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayTransition {
+    pub tick: u64,
+    pub state: AppState,
+}
+
+// 1run分,InGameのFixedUpdateのtick毎
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayData {
+    pub seed: u64,
+    pub tick_hz: f64,
+    pub frames: Vec<ReplayFrame>,
+    #[serde(default)]
+    pub transitions: Vec<ReplayTransition>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Could not read or write the replay: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the replay: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("Could not serialize the replay: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+impl ReplayData {
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Resource, Default)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    // 入力を記録して,GameOverか終了時にpathへ保存
+    Record {
+        path: PathBuf,
+        data: ReplayData,
+    },
+    // 記録した入力を再生,menuは記録した遷移で進める
+    Playback {
+        data: ReplayData,
+        next_transition: usize,
+        diverged: bool,
+    },
+}
+
+// 再生中は実際の入力を使わない
+pub fn is_live_input(mode: Res<ReplayMode>) -> bool {
+    !matches!(*mode, ReplayMode::Playback { .. })
+}
+
+fn is_playback(mode: Res<ReplayMode>) -> bool {
+    !is_live_input(mode)
+}
+
+// 起動時に指定されたpath
+#[derive(Resource, Clone, Default)]
+struct ReplayArgs {
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+}
+
+#[derive(Default, Clone)]
+pub struct ReplayPlugin {
+    args: ReplayArgs,
+}
+impl ReplayPlugin {
+    // --record <path> / --replay <path>
+    pub fn from_args(args: &[String]) -> Self {
+        let value = |name: &str| {
+            args.iter()
+                .position(|a| a == name)
+                .and_then(|i| args.get(i + 1))
+                .map(PathBuf::from)
+        };
+        Self {
+            args: ReplayArgs {
+                record: value("--record"),
+                replay: value("--replay"),
+            },
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayMode>()
+            .insert_resource(self.args.clone())
+            .add_systems(Startup, replay_startup_system)
+            .add_systems(
+                OnExit(AppState::Title),
+                replay_begin_system.after(crate::game_rng::setup_game_rng_system),
+            )
+            .add_systems(
+                FixedPreUpdate,
+                (replay_record_system, replay_playback_system).run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                replay_menu_system
+                    .run_if(is_playback)
+                    .run_if(not(in_state(AppState::InGame))),
+            )
+            .add_systems(Last, replay_transition_system)
+            .add_systems(Last, replay_save_system.run_if(on_event::<AppExit>()));
+    }
+}
+
+// 再生時は記録したseed,tick rateで始める
+fn replay_startup_system(
+    args: Res<ReplayArgs>,
+    mut mode: ResMut<ReplayMode>,
+    mut run_seed: ResMut<RunSeed>,
+    mut config: ResMut<SimulationConfig>,
+) {
+    if let Some(path) = &args.replay {
+        match ReplayData::load(path) {
+            Ok(data) => {
+                run_seed.0 = Some(data.seed);
+                config.tick_hz = data.tick_hz;
+                *mode = ReplayMode::Playback {
+                    data,
+                    next_transition: 0,
+                    diverged: false,
+                };
+                return;
+            }
+            Err(e) => error!("replay {}: {e}", path.display()),
+        }
+    }
+    if let Some(path) = &args.record {
+        *mode = ReplayMode::Record {
+            path: path.clone(),
+            data: ReplayData::default(),
+        };
+    }
+}
+
+fn replay_begin_system(
+    mut mode: ResMut<ReplayMode>,
+    game_rng: Res<GameRng>,
+    config: Res<SimulationConfig>,
+) {
+    match &mut *mode {
+        ReplayMode::Record { data, .. } => {
+            *data = ReplayData {
+                seed: game_rng.seed(),
+                tick_hz: config.tick_hz,
+                frames: Vec::new(),
+                transitions: Vec::new(),
+            };
+        }
+        ReplayMode::Playback {
+            next_transition,
+            diverged,
+            ..
+        } => {
+            *next_transition = 0;
+            *diverged = false;
+        }
+        ReplayMode::Off => {}
+    }
+}
+
+fn replay_record_system(
+    mut mode: ResMut<ReplayMode>,
+    input: Res<ButtonInput<InputMngBtn>>,
+    aim: Res<AimInput>,
+) {
+    if let ReplayMode::Record { data, .. } = &mut *mode {
+        data.frames.push(ReplayFrame::capture(&input, &aim));
+    }
+}
+
+fn replay_playback_system(
+    mode: Res<ReplayMode>,
+    tick: Res<RunTick>,
+    mut input: ResMut<ButtonInput<InputMngBtn>>,
+    mut aim: ResMut<AimInput>,
+) {
+    if let ReplayMode::Playback { data, .. } = &*mode {
+        // 最後まで再生したら入力なし
+        let tick = tick.0 as usize;
+        let frame = data.frames.get(tick).copied().unwrap_or_default();
+        if tick == data.frames.len() {
+            info!("replay finished");
+        }
+        frame.apply(&mut input, &mut aim);
+    }
+}
+
+// 記録時は遷移を残し,再生時は記録と同じtick,同じ状態か確かめる
+fn replay_transition_system(
+    mut mode: ResMut<ReplayMode>,
+    tick: Res<RunTick>,
+    mut events: EventReader<StateTransitionEvent<AppState>>,
+) {
+    for event in events.read() {
+        let transition = ReplayTransition {
+            tick: tick.0,
+            state: event.after,
+        };
+        match &mut *mode {
+            ReplayMode::Record { path, data } => {
+                data.transitions.push(transition);
+                // GameOverまでの遷移を入れて保存
+                if transition.state == AppState::GameOver {
+                    save_replay(path, data);
+                }
+            }
+            ReplayMode::Playback {
+                data,
+                next_transition,
+                diverged,
+            } => {
+                let recorded = data.transitions.get(*next_transition);
+                if recorded != Some(&transition) && !*diverged {
+                    warn!("replay diverged: {transition:?}, recorded {recorded:?}");
+                    *diverged = true;
+                }
+                *next_transition += 1;
+            }
+            ReplayMode::Off => {}
+        }
+    }
+}
+
+// 再生中はmenuを記録した次の状態へ進める,記録が尽きたらそのまま
+fn replay_menu_system(mode: Res<ReplayMode>, mut next_state: ResMut<NextState<AppState>>) {
+    if let ReplayMode::Playback {
+        data,
+        next_transition,
+        ..
+    } = &*mode
+    {
+        if let Some(transition) = data.transitions.get(*next_transition) {
+            next_state.set(transition.state);
+        }
+    }
+}
+
+// headless,attract modeではボタン待ちの画面を進める
+pub fn auto_advance_menu_system(
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match state.get() {
+        AppState::Title | AppState::Shop => next_state.set(AppState::InGame),
+        AppState::LevelUp => next_state.set(AppState::Shop),
+        AppState::InGame | AppState::GameOver => {}
+    }
+}
+
+fn replay_save_system(mode: Res<ReplayMode>) {
+    if let ReplayMode::Record { path, data } = &*mode {
+        save_replay(path, data);
+    }
+}
+
+fn save_replay(path: &Path, data: &ReplayData) {
+    if data.frames.is_empty() {
+        return;
+    }
+    match data.save(path) {
+        Ok(()) => info!("replay saved:{}", path.display()),
+        Err(e) => error!("replay {}: {e}", path.display()),
+    }
+}
@@ -1,8 +1,48 @@
+use crate::data_asset::DataAsset;
+use crate::enemy::{EnemyArchetype, EnemyArchetypes};
+use bevy::asset::{AssetPath, ErasedLoadedAsset};
 use bevy::prelude::*;
 
 #[derive(serde::Deserialize, Asset, TypePath)]
 pub struct GameLevel {
     positions: Vec<[f32; 3]>,
+    enemies: Vec<String>,        //敵の種類のファイル,まとめて使う
+    pub waves: Vec<Vec<String>>, //waveごとに出る敵の名前,最後のwaveを繰り返す
+    #[serde(skip)]
+    pub archetypes: Vec<EnemyArchetype>, //enemiesから読んだもの
+}
+impl DataAsset for GameLevel {
+    fn includes(&self) -> Vec<String> {
+        self.enemies.clone()
+    }
+    fn include(&mut self, _path: &AssetPath, asset: ErasedLoadedAsset) -> Result<(), String> {
+        let enemies = asset
+            .take::<EnemyArchetypes>()
+            .ok_or("not an enemies file")?;
+        self.archetypes.extend(enemies.list);
+        Ok(())
+    }
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.enemies.is_empty() {
+            errors.push("enemies is empty".into());
+        }
+        for (i, a) in self.archetypes.iter().enumerate() {
+            if self.archetypes[..i].iter().any(|b| b.name == a.name) {
+                errors.push(format!("{} is in two enemies files", a.name));
+            }
+        }
+        if self.waves.is_empty() {
+            errors.push("waves is empty".into());
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            if wave.is_empty() {
+                errors.push(format!("waves[{i}] has no enemy"));
+            }
+            for name in wave {
+                if self.archetypes.iter().all(|a| a.name != *name) {
+                    errors.push(format!("waves[{i}]: unknown enemy {name}"));
+                }
+            }
+        }
+    }
 }
-#[derive(Resource)]
-pub struct GameLevelHandle(pub Handle<GameLevel>);
@@ -1,85 +0,0 @@
-/*! bevy_common_assets | MIT License | https://github.com/NiklasEi/bevy_common_assets/blob/main/LICENSE-MIT */
-//https://github.com/NiklasEi/bevy_common_assets/blob/main/src/ron.rs
-
-use bevy::app::{App, Plugin};
-use bevy::asset::io::Reader;
-use bevy::asset::{Asset, AssetApp, AssetLoader, AsyncReadExt, BoxedFuture, LoadContext};
-use ron::de::from_bytes;
-use std::marker::PhantomData;
-use thiserror::Error;
-
-/// Plugin to load your asset type `A` from ron files.
-pub struct RonAssetPlugin<A> {
-    extensions: Vec<&'static str>,
-    _marker: PhantomData<A>,
-}
-
-impl<A> Plugin for RonAssetPlugin<A>
-where
-    for<'de> A: serde::Deserialize<'de> + Asset,
-{
-    fn build(&self, app: &mut App) {
-        app.init_asset::<A>()
-            .register_asset_loader(RonAssetLoader::<A> {
-                extensions: self.extensions.clone(),
-                _marker: PhantomData,
-            });
-    }
-}
-
-impl<A> RonAssetPlugin<A>
-where
-    for<'de> A: serde::Deserialize<'de> + Asset,
-{
-    /// Create a new plugin that will load assets from files with the given extensions.
-    pub fn new(extensions: &[&'static str]) -> Self {
-        Self {
-            extensions: extensions.to_owned(),
-            _marker: PhantomData,
-        }
-    }
-}
-
-struct RonAssetLoader<A> {
-    extensions: Vec<&'static str>,
-    _marker: PhantomData<A>,
-}
-
-/// Possible errors that can be produced by [`RonAssetLoader`]
-#[non_exhaustive]
-#[derive(Debug, Error)]
-pub enum RonLoaderError {
-    /// An [IO Error](std::io::Error)
-    #[error("Could not read the file: {0}")]
-    Io(#[from] std::io::Error),
-    /// A [RON Error](serde_ron::error::SpannedError)
-    #[error("Could not parse RON: {0}")]
-    RonError(#[from] ron::error::SpannedError),
-}
-
-impl<A> AssetLoader for RonAssetLoader<A>
-where
-    for<'de> A: serde::Deserialize<'de> + Asset,
-{
-    type Asset = A;
-    type Settings = ();
-    type Error = RonLoaderError;
-
-    fn load<'a>(
-        &'a self,
-        reader: &'a mut Reader,
-        _settings: &'a (),
-        _load_context: &'a mut LoadContext,
-    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
-        Box::pin(async move {
-            let mut bytes = Vec::new();
-            reader.read_to_end(&mut bytes).await?;
-            let asset = from_bytes::<A>(&bytes)?;
-            Ok(asset)
-        })
-    }
-
-    fn extensions(&self) -> &[&str] {
-        &self.extensions
-    }
-}
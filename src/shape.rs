@@ -0,0 +1,383 @@
+use crate::sparse_grid::Aabb;
+use bevy::prelude::*;
+
+/// Max vertices of a collider core, a swept box has at most 8
+const MAX_POINTS: usize = 8;
+
+/// Collider shape, every shape is a core (point, segment or convex polygon) rounded by a radius
+///
+/// `Circle` with radius is a circle, `Capsule` a capsule, boxes with radius 0 are plain boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize)]
+pub enum Shape {
+    #[default]
+    Circle,
+    /// Segment along the local x axis
+    Capsule { half_length: f32 },
+    /// Axis aligned box, ignores the rotation
+    Box { half_width: f32, half_height: f32 },
+    /// Box rotated with the transform
+    OrientedBox { half_width: f32, half_height: f32 },
+}
+
+/// Shape placed in the world, for the narrowphase
+#[derive(Debug, Clone, Copy)]
+pub struct Collider {
+    /// Core vertices, counter clockwise when there are 3 or more
+    points: [Vec2; MAX_POINTS],
+    len: usize,
+    radius: f32,
+}
+
+/// Contact between two colliders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// From the first collider to the second
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+impl Collider {
+    pub fn new(shape: Shape, radius: f32, tf: &Transform) -> Self {
+        let pos = tf.translation.xy();
+        let rot = (tf.rotation * Vec3::X).xy().normalize_or_zero();
+        let rotate = |v: Vec2| pos + rot.rotate(v);
+        let corners = |hw: f32, hh: f32| {
+            [
+                Vec2::new(-hw, -hh),
+                Vec2::new(hw, -hh),
+                Vec2::new(hw, hh),
+                Vec2::new(-hw, hh),
+            ]
+        };
+        match shape {
+            Shape::Circle => Self::from_points(&[pos], radius),
+            Shape::Capsule { half_length } => Self::from_points(
+                &[
+                    rotate(Vec2::new(-half_length, 0.)),
+                    rotate(Vec2::new(half_length, 0.)),
+                ],
+                radius,
+            ),
+            Shape::Box {
+                half_width,
+                half_height,
+            } => Self::from_points(&corners(half_width, half_height).map(|v| pos + v), radius),
+            Shape::OrientedBox {
+                half_width,
+                half_height,
+            } => Self::from_points(&corners(half_width, half_height).map(rotate), radius),
+        }
+    }
+
+    fn from_points(points: &[Vec2], radius: f32) -> Self {
+        let mut collider = Self {
+            points: [Vec2::ZERO; MAX_POINTS],
+            len: points.len(),
+            radius,
+        };
+        collider.points[..points.len()].copy_from_slice(points);
+        collider
+    }
+
+    /// Core vertices, a point, a segment or a convex polygon
+    pub fn core(&self) -> &[Vec2] {
+        &self.points[..self.len]
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Bounding box, used for the broadphase
+    pub fn aabb(&self) -> Aabb {
+        let (min, max) = self
+            .core()
+            .iter()
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), p| {
+                (min.min(*p), max.max(*p))
+            });
+        Aabb {
+            min: min - Vec2::splat(self.radius),
+            max: max + Vec2::splat(self.radius),
+        }
+    }
+
+    /// Same collider moved by `delta`
+    pub fn translated(&self, delta: Vec2) -> Self {
+        let mut moved = *self;
+        for p in &mut moved.points[..self.len] {
+            *p += delta;
+        }
+        moved
+    }
+
+    /// Area covered while moving by `delta`, the convex hull of both positions
+    pub fn swept(&self, delta: Vec2) -> Self {
+        let mut points = [Vec2::ZERO; MAX_POINTS * 2];
+        for (i, p) in self.core().iter().enumerate() {
+            points[i * 2] = *p;
+            points[i * 2 + 1] = *p + delta;
+        }
+        let (hull, len) = convex_hull(&mut points[..self.len * 2]);
+        Self::from_points(&hull[..len], self.radius)
+    }
+
+    // 辺,点は長さ0の辺,線分は1本
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let core = self.core();
+        let count = if core.len() <= 2 { 1 } else { core.len() };
+        (0..count).map(move |i| (core[i], core[(i + 1) % core.len()]))
+    }
+
+    // 分離軸の候補,辺の法線
+    fn axes(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.edges()
+            .filter_map(|(a, b)| (b - a).perp().try_normalize())
+    }
+
+    // coreの多角形の内側か
+    fn contains(&self, p: Vec2) -> bool {
+        self.len >= 3 && self.edges().all(|(a, b)| (b - a).perp_dot(p - a) >= 0.)
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        self.core()
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), p| {
+                let d = p.dot(axis);
+                (min.min(d), max.max(d))
+            })
+    }
+}
+
+/// Narrowphase test between any two shapes
+pub fn contact(a: &Collider, b: &Collider) -> Option<Contact> {
+    let target = a.radius + b.radius;
+    // 円同士,一番多いので先に
+    if a.len == 1 && b.len == 1 {
+        let diff = b.points[0] - a.points[0];
+        let d = diff.length();
+        return (d > 0. && d <= target).then(|| Contact {
+            normal: diff / d,
+            depth: target - d,
+        });
+    }
+    let overlapping = a.contains(b.points[0]) || b.contains(a.points[0]);
+    if !overlapping {
+        // coreが離れていれば,一番近い点同士で判定
+        let (pa, pb) = a
+            .edges()
+            .flat_map(|ea| b.edges().map(move |eb| closest_segment_segment(ea, eb)))
+            .min_by(|x, y| {
+                x.0.distance_squared(x.1)
+                    .total_cmp(&y.0.distance_squared(y.1))
+            })?;
+        let diff = pb - pa;
+        let d = diff.length();
+        if d > target {
+            return None;
+        }
+        if d > 0. {
+            return Some(Contact {
+                normal: diff / d,
+                depth: target - d,
+            });
+        }
+    }
+    // coreが重なっている,分離軸で一番浅い方向に押し出す
+    let mut best: Option<Contact> = None;
+    for axis in a.axes().chain(b.axes()) {
+        let (amin, amax) = a.project(axis);
+        let (bmin, bmax) = b.project(axis);
+        let (overlap, normal) = if amax - bmin < bmax - amin {
+            (amax - bmin, axis)
+        } else {
+            (bmax - amin, -axis)
+        };
+        if best.is_none_or(|c| overlap + target < c.depth) {
+            best = Some(Contact {
+                normal,
+                depth: overlap + target,
+            });
+        }
+    }
+    best // 同じ位置の点同士はNone
+}
+
+// 2つの線分の一番近い点
+fn closest_segment_segment((p1, q1): (Vec2, Vec2), (p2, q2): (Vec2, Vec2)) -> (Vec2, Vec2) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0., 0.)
+    } else if a <= f32::EPSILON {
+        (0., (f / e).clamp(0., 1.))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0., 1.), 0.)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            let s = if denom > 0. {
+                ((b * f - c * e) / denom).clamp(0., 1.)
+            } else {
+                0. //平行
+            };
+            let t = (b * s + f) / e;
+            if t < 0. {
+                ((-c / a).clamp(0., 1.), 0.)
+            } else if t > 1. {
+                (((b - c) / a).clamp(0., 1.), 1.)
+            } else {
+                (s, t)
+            }
+        }
+    };
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+// 凸包,反時計回り,monotone chain
+fn convex_hull(points: &mut [Vec2]) -> ([Vec2; MAX_POINTS], usize) {
+    points.sort_unstable_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    let mut hull = [Vec2::ZERO; MAX_POINTS * 2 + 1];
+    let mut k = 0;
+    let push = |hull: &mut [Vec2], k: &mut usize, lower: usize, p: Vec2| {
+        while *k >= lower && (hull[*k - 1] - hull[*k - 2]).perp_dot(p - hull[*k - 2]) <= 0. {
+            *k -= 1;
+        }
+        hull[*k] = p;
+        *k += 1;
+    };
+    for p in points.iter() {
+        push(&mut hull, &mut k, 2, *p);
+    }
+    let lower = k + 1;
+    for p in points.iter().rev().skip(1) {
+        push(&mut hull, &mut k, lower, *p);
+    }
+    // 最後は最初の点と同じ,全部同じ点なら1つ
+    let len = (k - 1).clamp(1, MAX_POINTS);
+    let mut out = [Vec2::ZERO; MAX_POINTS];
+    out[..len].copy_from_slice(&hull[..len]);
+    (out, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::vec2;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn at(shape: Shape, radius: f32, pos: Vec2, angle: f32) -> Collider {
+        let tf =
+            Transform::from_translation(pos.extend(0.)).with_rotation(Quat::from_rotation_z(angle));
+        Collider::new(shape, radius, &tf)
+    }
+
+    #[test]
+    fn circle_vs_circle() {
+        let a = at(Shape::Circle, 2., vec2(0., 0.), 0.);
+        let b = at(Shape::Circle, 3., vec2(4., 0.), 0.);
+        let c = contact(&a, &b).unwrap();
+        assert_eq!(c.normal, vec2(1., 0.));
+        assert!((c.depth - 1.).abs() < 1e-5);
+        assert!(contact(&a, &at(Shape::Circle, 3., vec2(6., 0.), 0.)).is_none());
+        // 同じ位置は押し出す方向が無い
+        assert!(contact(&a, &a).is_none());
+    }
+
+    #[test]
+    fn box_vs_box() {
+        let shape = Shape::Box {
+            half_width: 2.,
+            half_height: 1.,
+        };
+        let a = at(shape, 0., vec2(0., 0.), 0.);
+        let b = at(shape, 0., vec2(3., 0.5), 0.);
+        let c = contact(&a, &b).unwrap();
+        assert_eq!(c.normal, vec2(1., 0.));
+        assert!((c.depth - 1.).abs() < 1e-5);
+        assert!(contact(&a, &at(shape, 0., vec2(5., 0.), 0.1)).is_none());
+        assert_eq!(
+            a.aabb(),
+            Aabb {
+                min: vec2(-2., -1.),
+                max: vec2(2., 1.),
+            }
+        );
+    }
+
+    #[test]
+    fn oriented_box_rotates() {
+        let shape = Shape::OrientedBox {
+            half_width: 4.,
+            half_height: 1.,
+        };
+        // 縦向きなら上の円に届く,横向きなら届かない
+        let circle = at(Shape::Circle, 1., vec2(0., 4.5), 0.);
+        assert!(contact(&at(shape, 0., Vec2::ZERO, 0.), &circle).is_none());
+        let c = contact(&at(shape, 0., Vec2::ZERO, FRAC_PI_2), &circle).unwrap();
+        assert!((c.normal - vec2(0., 1.)).length() < 1e-5);
+        assert!((c.depth - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn capsule_vs_circle() {
+        let capsule = at(Shape::Capsule { half_length: 5. }, 1., Vec2::ZERO, 0.);
+        let c = contact(&capsule, &at(Shape::Circle, 1., vec2(3., 1.5), 0.)).unwrap();
+        assert_eq!(c.normal, vec2(0., 1.));
+        assert!((c.depth - 0.5).abs() < 1e-5);
+        assert!(contact(&capsule, &at(Shape::Circle, 1., vec2(7.5, 0.), 0.)).is_none());
+        // 中心がcoreの上でも押し出せる
+        let c = contact(&at(Shape::Circle, 1., vec2(2., 0.), 0.), &capsule).unwrap();
+        assert!((c.depth - 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn circle_inside_box() {
+        let shape = Shape::Box {
+            half_width: 10.,
+            half_height: 10.,
+        };
+        let c = contact(
+            &at(shape, 0., Vec2::ZERO, 0.),
+            &at(Shape::Circle, 1., vec2(8., 0.), 0.),
+        )
+        .unwrap();
+        assert_eq!(c.normal, vec2(1., 0.));
+        assert!((c.depth - 3.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn swept_does_not_tunnel() {
+        let bullet = at(
+            Shape::OrientedBox {
+                half_width: 2.,
+                half_height: 1.,
+            },
+            0.,
+            vec2(20., 0.),
+            0.,
+        );
+        let wall = at(
+            Shape::Box {
+                half_width: 1.,
+                half_height: 10.,
+            },
+            0.,
+            Vec2::ZERO,
+            0.,
+        );
+        assert!(contact(&bullet, &wall).is_none());
+        // 前のframeは反対側にいた
+        let swept = bullet.swept(vec2(-40., 0.));
+        assert_eq!(swept.len, 4);
+        assert!(contact(&swept, &wall).is_some());
+        assert!(contact(&bullet.swept(vec2(0., 30.)), &wall).is_none());
+    }
+}
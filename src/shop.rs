@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::AppState;
+use crate::{
+    inputmng::{InputMngBtn, MENU_CONFIRM},
+    AppState,
+};
 
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -55,12 +58,17 @@ pub fn setup_shop(mut commands: Commands) {
 }
 
 pub fn shop_system(
+    input: Res<ButtonInput<InputMngBtn>>,
     mut next_state: ResMut<NextState<AppState>>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
+    // ボタンが無くても決定で進む
+    if input.just_pressed(MENU_CONFIRM) {
+        next_state.set(AppState::InGame);
+    }
     for (interaction, mut color) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
@@ -1,4 +1,4 @@
-use crate::{components::*, GameConfig};
+use crate::{components::*, pool::Inactive, shape::Collider, GameConfig};
 use bevy::prelude::*;
 
 pub struct ShowDebugPlugin;
@@ -6,10 +6,7 @@ pub struct ShowDebugPlugin;
 impl Plugin for ShowDebugPlugin {
     fn build(&self, app: &mut App) {
         if cfg!(debug_assertions) {
-            app.add_systems(
-                Update,
-                (show_bg_gizmo_system, show_colli_gizmo_system).in_set(GameSystemSet::PostUpdate),
-            );
+            app.add_systems(Update, (show_bg_gizmo_system, show_colli_gizmo_system));
         }
     }
 }
@@ -27,16 +24,31 @@ fn show_bg_gizmo_system(mut gizmos: Gizmos) {
 
 fn show_colli_gizmo_system(
     mut gizmos: Gizmos,
-    query: Query<(&Transform, &CollideCircle)>,
+    query: Query<(&Transform, &CollideCircle), Without<Inactive>>,
+    hit_query: Query<(&Transform, &HitCircle), Without<Inactive>>,
     game_config: Query<&GameConfig>,
 ) {
     // show collision
     if cfg!(debug_assertions) && game_config.get_single().unwrap().dbg_show_collision {
         for (transform, colli) in query.iter() {
-            let pos = transform.translation.xy();
-            gizmos
-                .circle_2d(pos, colli.radius, Color::ORANGE)
-                .segments(16);
+            draw_collider(&mut gizmos, &colli.collider(transform), Color::ORANGE);
+        }
+        for (transform, hit) in hit_query.iter() {
+            draw_collider(&mut gizmos, &hit.collider(transform), Color::YELLOW);
+        }
+    }
+}
+
+// coreの輪郭と,角の丸みを円で
+fn draw_collider(gizmos: &mut Gizmos, collider: &Collider, color: Color) {
+    let core = collider.core();
+    if core.len() >= 2 {
+        let closed = core.iter().chain(core.first()).copied();
+        gizmos.linestrip_2d(closed, color);
+    }
+    if collider.radius() > 0. {
+        for p in core {
+            gizmos.circle_2d(*p, collider.radius(), color).segments(16);
         }
     }
 }
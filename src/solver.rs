@@ -0,0 +1,446 @@
+use crate::{
+    broadphase::Broadphase2d,
+    components::*,
+    physics::{PhysicsResource, SHM},
+    physics_config::PhysicsConfig,
+    pool::Inactive,
+    shape::{self, Collider, Contact},
+};
+use bevy::{ecs::entity::EntityHashMap, prelude::*, tasks::ComputeTaskPool};
+
+/// 並列で解く時,1taskで解くbody数
+const CHUNK_SIZE: usize = 256;
+/// これより浅いめり込みは押し出さない,密集が震えないように
+const CONTACT_SLOP: f32 = 0.05;
+/// 色分けするcellの大きさ,敵の直径より十分大きく
+const BATCH_CELL_SIZE: f32 = 32.;
+/// 2x2の色,同じ色のcellは1cell以上離れている
+const COLOURS: usize = 4;
+
+// 1body分,pass開始時の状態
+#[derive(Debug, Clone, Copy)]
+struct SolverBody {
+    collider: Collider, //move_vecを足した予測位置
+    inv_mass: f32,
+    velocity: Vec2,
+    restitution: f32,
+    friction: f32,
+    filter: CollisionFilter,
+}
+impl SolverBody {
+    fn new(
+        tf: &Transform,
+        colli: &CollideCircle,
+        obj: &PhysicalObj,
+        config: &PhysicsConfig,
+    ) -> Self {
+        Self {
+            collider: colli.collider(tf).translated(obj.move_vec),
+            inv_mass: obj.inv_mass,
+            velocity: obj.velocity,
+            restitution: obj.restitution.unwrap_or(config.restitution),
+            friction: obj.friction.unwrap_or(config.friction),
+            filter: colli.filter,
+        }
+    }
+}
+
+/// Result for one body
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BodyOut {
+    /// Position correction, add to `move_vec`
+    pub offset: Vec2,
+    /// Velocity change, add to `old_move_vec`
+    pub impulse: Vec2,
+    pub contacts: u32,
+}
+
+/// Position based contact solver
+///
+/// Bodies are batched by a red-black like 2x2 colouring of the cells they are in. The batches
+/// of one colour are solved together from the positions the previous colours left (Gauss-Seidel
+/// between colours), each body only writes its own result, so the serial and parallel versions
+/// give the same result without locks.
+#[derive(Default)]
+pub struct ContactSolver {
+    bodies: Vec<SolverBody>,
+    index: EntityHashMap<usize>,
+    pairs: Vec<(Entity, Entity)>,
+    starts: Vec<usize>, //bodyのneighborsの範囲,bodies.len()+1個
+    neighbors: Vec<usize>,
+    colour_starts: [usize; COLOURS + 1], //色ごとのbodiesの範囲
+    out: Vec<BodyOut>,
+    next: Vec<BodyOut>,
+}
+
+// 1回の反復で全bodyが見る値
+struct Step<'a> {
+    bodies: &'a [SolverBody],
+    starts: &'a [usize],
+    neighbors: &'a [usize],
+    prev: &'a [BodyOut],
+    relaxation: f32,
+    first: bool,
+}
+
+impl ContactSolver {
+    fn clear(&mut self) {
+        self.bodies.clear();
+        self.index.clear();
+    }
+
+    fn push(&mut self, entity: Entity, body: SolverBody) {
+        self.index.insert(entity, self.bodies.len());
+        self.bodies.push(body);
+    }
+
+    // cellの色,cellの順に並べ直す.同じcellのbodyは続く
+    fn batch(&mut self) {
+        let key = |b: &SolverBody| {
+            let aabb = b.collider.aabb();
+            let cell = ((aabb.min + aabb.max) * (0.5 / BATCH_CELL_SIZE))
+                .floor()
+                .as_ivec2();
+            ((cell.x & 1 | (cell.y & 1) << 1) as usize, cell.y, cell.x)
+        };
+        // 同じcellの中は入れた順
+        let mut order: Vec<_> = self.index.drain().collect();
+        order.sort_unstable_by_key(|&(_, i)| (key(&self.bodies[i]), i));
+        let bodies = std::mem::take(&mut self.bodies);
+        for &(entity, i) in &order {
+            self.push(entity, bodies[i]);
+        }
+        for c in 0..=COLOURS {
+            self.colour_starts[c] = self.bodies.partition_point(|b| key(b).0 < c);
+        }
+    }
+
+    // broadphaseの組から,押し合うものだけ隣接listにする
+    fn link(&mut self, sg2: &dyn Broadphase2d) {
+        self.batch();
+        sg2.pairs_into(&mut self.pairs);
+        let n = self.bodies.len();
+        self.starts.clear();
+        self.starts.resize(n + 1, 0);
+        self.neighbors.clear();
+        let (bodies, index) = (&self.bodies, &self.index);
+        let linked = |(e0, e1): &(Entity, Entity)| {
+            let (i, j) = (*index.get(e0)?, *index.get(e1)?);
+            bodies[i]
+                .filter
+                .collides(&bodies[j].filter)
+                .then_some((i, j))
+        };
+        // 数えてから詰める
+        for (i, j) in self.pairs.iter().filter_map(linked) {
+            self.starts[i + 1] += 1;
+            self.starts[j + 1] += 1;
+        }
+        for i in 0..n {
+            self.starts[i + 1] += self.starts[i];
+        }
+        self.neighbors.resize(self.starts[n], 0);
+        let mut cursor = self.starts.clone();
+        for (i, j) in self.pairs.iter().filter_map(linked) {
+            self.neighbors[cursor[i]] = j;
+            cursor[i] += 1;
+            self.neighbors[cursor[j]] = i;
+            cursor[j] += 1;
+        }
+        // 足す順を揃える,broadphaseの種類で結果が変わらないように
+        for i in 0..n {
+            self.neighbors[self.starts[i]..self.starts[i + 1]].sort_unstable();
+        }
+    }
+
+    fn solve(&mut self, iterations: u32, relaxation: f32, parallel: bool) {
+        let n = self.bodies.len();
+        self.out.clear();
+        self.out.resize(n, BodyOut::default());
+        self.next.clear();
+        self.next.resize(n, BodyOut::default());
+        for it in 0..iterations.max(1) {
+            for c in 0..COLOURS {
+                let range = self.colour_starts[c]..self.colour_starts[c + 1];
+                let step = Step {
+                    bodies: &self.bodies,
+                    starts: &self.starts,
+                    neighbors: &self.neighbors,
+                    prev: &self.out,
+                    relaxation,
+                    first: it == 0,
+                };
+                // この色のbodyは自分の結果だけ書く,読むのは前の色までの結果
+                let next = &mut self.next[range.clone()];
+                if parallel {
+                    let step = &step;
+                    ComputeTaskPool::get().scope(|scope| {
+                        for (k, chunk) in next.chunks_mut(CHUNK_SIZE).enumerate() {
+                            let first = range.start + k * CHUNK_SIZE;
+                            scope.spawn(async move {
+                                for (i, out) in (first..).zip(chunk.iter_mut()) {
+                                    *out = step.solve_body(i);
+                                }
+                            });
+                        }
+                    });
+                } else {
+                    for (i, out) in range.clone().zip(next.iter_mut()) {
+                        *out = step.solve_body(i);
+                    }
+                }
+                self.out[range.clone()].copy_from_slice(&self.next[range]);
+            }
+        }
+    }
+
+    fn result(&self, entity: Entity) -> Option<BodyOut> {
+        self.index.get(&entity).map(|i| self.out[*i])
+    }
+}
+
+impl Step<'_> {
+    fn solve_body(&self, i: usize) -> BodyOut {
+        let b0 = &self.bodies[i];
+        let prev = self.prev[i];
+        let c0 = b0.collider.translated(prev.offset);
+        let mut out = prev;
+        let (mut delta, mut count) = (Vec2::ZERO, 0);
+        for &j in &self.neighbors[self.starts[i]..self.starts[i + 1]] {
+            let b1 = &self.bodies[j];
+            let together_inv_mass = b0.inv_mass + b1.inv_mass;
+            if together_inv_mass <= 0. {
+                continue; //両方動かない
+            }
+            let c1 = b1.collider.translated(self.prev[j].offset);
+            let Some(Contact { normal, depth }) = shape::contact(&c0, &c1) else {
+                continue;
+            };
+            // 質量の逆数の比で押し出す
+            delta -= normal * (depth - CONTACT_SLOP).max(0.) * (b0.inv_mass / together_inv_mass);
+            count += 1;
+            // 反発,摩擦はpass開始時の接触で1回だけ
+            if self.first {
+                out.impulse += velocity_impulse(b0, b1, normal);
+                out.contacts += 1;
+            }
+        }
+        // 接触の数で平均する,押し出しが重なって飛ばないように
+        if count > 0 {
+            out.offset += delta * (self.relaxation / count as f32);
+        }
+        out
+    }
+}
+
+// b0側のold_move_vecの変化量,近づいている時だけ
+fn velocity_impulse(b0: &SolverBody, b1: &SolverBody, n: Vec2) -> Vec2 {
+    let together_inv_mass = b0.inv_mass + b1.inv_mass;
+    let v = b0.velocity - b1.velocity;
+    let vn = v.dot(n);
+    if vn <= 0. {
+        return Vec2::ZERO;
+    }
+    let restitution = b0.restitution.max(b1.restitution);
+    let jn = (1.0 + restitution) * vn / together_inv_mass;
+    // 接線方向の速度を,法線方向の力に比例した分まで止める
+    let vt = v - n * vn;
+    let friction = (b0.friction * b1.friction).sqrt();
+    let jt = (vt.length() / together_inv_mass).min(friction * jn);
+    (n * jn + vt.normalize_or_zero() * jt) * b0.inv_mass
+}
+
+// 接触の解決,位置の補正をmove_vec,反発と摩擦をold_move_vecに足す
+pub fn solve_contacts_system(
+    mut query: Query<(Entity, &Transform, &CollideCircle, &mut PhysicalObj), Without<Inactive>>,
+    shm: Res<SHM>,
+    physics_resource: Res<PhysicsResource>,
+    config: Res<PhysicsConfig>,
+    mut solver: Local<ContactSolver>,
+) {
+    solver.clear();
+    for (entity, tf, colli, obj) in query.iter() {
+        solver.push(entity, SolverBody::new(tf, colli, obj, &config));
+    }
+    solver.link(&*shm.sg2);
+    solver.solve(
+        config.solver_iterations,
+        config.solver_relaxation,
+        physics_resource.parallel_collision,
+    );
+    for (entity, _, _, mut obj) in query.iter_mut() {
+        if let Some(out) = solver.result(entity) {
+            obj.move_vec += out.offset;
+            obj.old_move_vec += out.impulse;
+            obj.collision_count += out.contacts;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sparse_grid::SparseGrid2d;
+    use bevy::{math::vec2, tasks::TaskPool};
+
+    fn body(pos: Vec2, velocity: Vec2, inv_mass: f32) -> SolverBody {
+        let obj = PhysicalObj {
+            inv_mass,
+            velocity,
+            ..default()
+        };
+        SolverBody::new(
+            &Transform::from_translation(pos.extend(0.)),
+            &CollideCircle::new(CollisionLayer::Enemy),
+            &obj,
+            &PhysicsConfig::default(),
+        )
+    }
+
+    fn solver(bodies: &[SolverBody]) -> ContactSolver {
+        let mut grid = SparseGrid2d::<8>::default();
+        let mut solver = ContactSolver::default();
+        for (i, b) in bodies.iter().enumerate() {
+            let entity = Entity::from_raw(i as u32);
+            grid.insert_aabb(b.collider.aabb(), entity);
+            solver.push(entity, *b);
+        }
+        solver.link(&grid);
+        solver
+    }
+
+    fn out(solver: &ContactSolver, i: u32) -> BodyOut {
+        solver.result(Entity::from_raw(i)).unwrap()
+    }
+
+    // めり込みの合計
+    fn total_depth(solver: &ContactSolver) -> f32 {
+        let moved: Vec<_> = (0..solver.bodies.len())
+            .map(|i| solver.bodies[i].collider.translated(solver.out[i].offset))
+            .collect();
+        let mut depth = 0.;
+        for (i, a) in moved.iter().enumerate() {
+            for b in &moved[i + 1..] {
+                if let Some(c) = shape::contact(a, b) {
+                    depth += c.depth;
+                }
+            }
+        }
+        depth
+    }
+
+    #[test]
+    fn pair_separates() {
+        let mut s = solver(&[
+            body(vec2(0., 0.), Vec2::ZERO, 1.),
+            body(vec2(6., 0.), Vec2::ZERO, 1.),
+        ]);
+        s.solve(1, 1., false);
+        let half = (2. - CONTACT_SLOP) * 0.5;
+        assert!((out(&s, 0).offset - vec2(-half, 0.)).length() < 1e-5);
+        assert!((out(&s, 1).offset - vec2(half, 0.)).length() < 1e-5);
+        assert_eq!(out(&s, 0).contacts, 1);
+    }
+
+    #[test]
+    fn static_body_does_not_move() {
+        let mut s = solver(&[
+            body(vec2(0., 0.), Vec2::ZERO, 0.),
+            body(vec2(6., 0.), Vec2::ZERO, 1.),
+        ]);
+        s.solve(1, 1., false);
+        assert_eq!(out(&s, 0).offset, Vec2::ZERO);
+        assert!((out(&s, 1).offset.x - (2. - CONTACT_SLOP)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn crowd_converges() {
+        // 格子に詰め込んだ群れ,反復するほど浅くなる
+        let bodies: Vec<_> = (0..100)
+            .map(|i| {
+                body(
+                    vec2((i % 10) as f32 * 7., (i / 10) as f32 * 7.),
+                    Vec2::ZERO,
+                    1.,
+                )
+            })
+            .collect();
+        let mut s = solver(&bodies);
+        let depths: Vec<_> = [1, 4, 16]
+            .into_iter()
+            .map(|iterations| {
+                s.solve(iterations, 1.5, false);
+                total_depth(&s)
+            })
+            .collect();
+        assert!(depths[1] < depths[0] && depths[2] < depths[1], "{depths:?}");
+        assert!(depths[2] < depths[0] * 0.75, "{depths:?}");
+    }
+
+    #[test]
+    fn serial_same_as_parallel() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let bodies: Vec<_> = (0..1000)
+            .map(|i| {
+                let pos = vec2((i % 40) as f32 * 6., (i / 40) as f32 * 6.);
+                body(pos, vec2(1., 0.), 1.)
+            })
+            .collect();
+        let mut s = solver(&bodies);
+        s.solve(4, 1., false);
+        let serial = s.out.clone();
+        s.solve(4, 1., true);
+        assert_eq!(serial, s.out);
+    }
+
+    #[test]
+    fn colour_batches_do_not_touch() {
+        // 同じ色の別のcellのbodyとは押し合わない
+        let bodies: Vec<_> = (0..400)
+            .map(|i| {
+                body(
+                    vec2((i % 20) as f32 * 7., (i / 20) as f32 * 7.),
+                    Vec2::ZERO,
+                    1.,
+                )
+            })
+            .collect();
+        let s = solver(&bodies);
+        assert_eq!(s.colour_starts[0], 0);
+        assert_eq!(s.colour_starts[COLOURS], bodies.len());
+        let colour = |i: usize| s.colour_starts.partition_point(|&start| start <= i) - 1;
+        let cell = |i: usize| {
+            let aabb = s.bodies[i].collider.aabb();
+            ((aabb.min + aabb.max) * (0.5 / BATCH_CELL_SIZE)).floor()
+        };
+        for i in 0..s.bodies.len() {
+            for &j in &s.neighbors[s.starts[i]..s.starts[i + 1]] {
+                assert!(colour(i) != colour(j) || cell(i) == cell(j), "{i} {j}");
+            }
+        }
+    }
+
+    #[test]
+    fn bounce_and_friction() {
+        // 近づいている時だけ跳ね返る
+        let approaching = |friction: f32| {
+            let mut b0 = body(vec2(0., 0.), vec2(1., 1.), 1.);
+            b0.friction = friction;
+            let mut b1 = body(vec2(7., 0.), Vec2::ZERO, 1.);
+            b1.friction = friction;
+            let mut s = solver(&[b0, b1]);
+            s.solve(1, 1., false);
+            out(&s, 0).impulse
+        };
+        let frictionless = approaching(0.);
+        assert!(frictionless.x > 0.);
+        assert_eq!(frictionless.y, 0.);
+        assert!(approaching(1.).y > 0.);
+        let mut s = solver(&[
+            body(vec2(0., 0.), vec2(-1., 0.), 1.),
+            body(vec2(7., 0.), Vec2::ZERO, 1.),
+        ]);
+        s.solve(1, 1., false);
+        assert_eq!(out(&s, 0).impulse, Vec2::ZERO);
+    }
+}
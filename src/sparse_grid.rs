@@ -28,25 +28,83 @@ impl Aabb {
 }
 
 type Key = (i32, i32);
+/// Cells covered by an Aabb, `start` inclusive and `end` exclusive
+type KeyRange = (Key, Key);
 
 /// A spatial container that allows querying for entities that share one or more grid cell
 #[derive(Default, Reflect, Debug, Clone, Resource)]
 pub struct SparseGrid2d<const TILE_SIZE: usize = 1> {
     map: HashMap<Key, SmallVec<[Entity; 16]>>,
+    /// Reverse index, the cells each entity was inserted in
+    index: HashMap<Entity, KeyRange>,
 }
 
 impl<const TILE_SIZE: usize> SparseGrid2d<TILE_SIZE> {
     /// Insert an entity in the given Aabb coordinates
     pub fn insert_aabb(&mut self, aabb: impl Into<Aabb>, entity: Entity) {
-        for key in KeyIter::new::<TILE_SIZE>(aabb) {
+        self.insert_range(KeyIter::range::<TILE_SIZE>(aabb.into()), entity);
+    }
+
+    fn insert_range(&mut self, range: KeyRange, entity: Entity) {
+        for key in KeyIter::from_range(range) {
             self.map.entry(key).or_default().push(entity);
         }
+        self.index.insert(entity, range);
     }
 
     /// Insert an entity at the given point coordinate
     pub fn insert_point(&mut self, point: Vec2, entity: Entity) {
         let key = Self::key_from_point(point);
         self.map.entry(key).or_default().push(entity);
+        self.index.insert(entity, (key, (key.0 + 1, key.1 + 1)));
+    }
+
+    /// Move an entity to `new_aabb`, from the cells it was inserted in
+    ///
+    /// Only the cells that differ are touched, nothing is done if the entity stays in the
+    /// same cells. Cells left empty are removed, so the map does not keep growing.
+    /// `old_aabb` is only a hint, the old cells come from the reverse index and an entity
+    /// not in the grid is inserted.
+    pub fn update(&mut self, entity: Entity, old_aabb: impl Into<Aabb>, new_aabb: impl Into<Aabb>) {
+        let new = KeyIter::range::<TILE_SIZE>(new_aabb.into());
+        // 同じcellのままなら索引と合っているかだけ見る
+        if KeyIter::range::<TILE_SIZE>(old_aabb.into()) == new
+            && self.index.get(&entity) == Some(&new)
+        {
+            return;
+        }
+        let Some(&old) = self.index.get(&entity) else {
+            self.insert_range(new, entity);
+            return;
+        };
+        if old == new {
+            return;
+        }
+        for key in KeyIter::from_range(old).filter(|key| !range_contains(new, *key)) {
+            self.remove_from_cell(key, entity);
+        }
+        for key in KeyIter::from_range(new).filter(|key| !range_contains(old, *key)) {
+            self.map.entry(key).or_default().push(entity);
+        }
+        self.index.insert(entity, new);
+    }
+
+    /// Remove an entity from all the cells it was inserted in
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(range) = self.index.remove(&entity) {
+            for key in KeyIter::from_range(range) {
+                self.remove_from_cell(key, entity);
+            }
+        }
+    }
+
+    fn remove_from_cell(&mut self, key: Key, entity: Entity) {
+        if let Some(cell) = self.map.get_mut(&key) {
+            cell.retain(|e| *e != entity);
+            if cell.is_empty() {
+                self.map.remove(&key);
+            }
+        }
     }
 
     /// Get an iterator with the entities in the grid cells covered by the given Aabb
@@ -71,15 +129,69 @@ impl<const TILE_SIZE: usize> SparseGrid2d<TILE_SIZE> {
             .copied()
     }
 
+    /// Get an iterator with the entities in the grid cells crossed by the segment, in cell order
+    /// from `start` to `end`
+    ///
+    /// may contain duplicates if some entities are in more than one grid cell
+    #[inline]
+    pub fn segment_iter(&'_ self, start: Vec2, end: Vec2) -> impl Iterator<Item = Entity> + '_ {
+        SegmentKeyIter::new::<TILE_SIZE>(start, end)
+            .filter_map(|key| self.map.get(&key))
+            .flatten()
+            .copied()
+    }
+
+    /// Same as `segment_iter`, from `origin` along `dir` for `max_dist`
+    #[inline]
+    pub fn ray_iter(
+        &'_ self,
+        origin: Vec2,
+        dir: Vec2,
+        max_dist: f32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.segment_iter(origin, origin + dir.normalize_or_zero() * max_dist)
+    }
+
     /// Creates a hash set with all the entities in the grid cells covered by the given Aabb
     #[inline]
     pub fn query_aabb(&self, aabb: impl Into<Aabb>) -> HashSet<Entity> {
         self.aabb_iter(aabb).collect()
     }
 
+    /// Writes the entities in the grid cells covered by the given Aabb into `out`
+    ///
+    /// `out` is cleared first and reused, so no allocation once it has grown.
+    /// The result is sorted and has no duplicates.
+    pub fn query_aabb_into(&self, aabb: impl Into<Aabb>, out: &mut Vec<Entity>) {
+        out.clear();
+        out.extend(self.aabb_iter(aabb));
+        out.sort_unstable();
+        out.dedup();
+    }
+
+    /// Writes every pair of entities that share at least one grid cell into `out`
+    ///
+    /// Each pair appears exactly once as `(a, b)` with `a < b`, even if the two
+    /// entities share several cells. `out` is cleared first and reused.
+    pub fn pairs_into(&self, out: &mut Vec<(Entity, Entity)>) {
+        out.clear();
+        for cell in self.map.values() {
+            for (i, &a) in cell.iter().enumerate() {
+                for &b in &cell[i + 1..] {
+                    if a != b {
+                        out.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+
     /// Remove all entities from the map
     pub fn clear(&mut self) {
         self.map.clear();
+        self.index.clear();
     }
 
     /// Remove all entities from the map, but keep the heap-allocated inner data structures
@@ -87,6 +199,7 @@ impl<const TILE_SIZE: usize> SparseGrid2d<TILE_SIZE> {
         for (_, vec) in self.map.iter_mut() {
             vec.clear()
         }
+        self.index.clear();
     }
 
     fn key_from_point(point: Vec2) -> Key {
@@ -97,6 +210,69 @@ impl<const TILE_SIZE: usize> SparseGrid2d<TILE_SIZE> {
     }
 }
 
+/// Grid cells crossed by a segment, in order (Amanatides-Woo DDA)
+pub struct SegmentKeyIter {
+    current: Key,
+    end: Key,
+    step: Key,
+    t_max: Vec2,   //次のcell境界までのt,x,y
+    t_delta: Vec2, //1cell進むのに必要なt
+    done: bool,
+}
+
+impl SegmentKeyIter {
+    pub fn new<const TILE_SIZE: usize>(start: Vec2, end: Vec2) -> Self {
+        // convert to key space
+        let s = TILE_SIZE as f32;
+        let p0 = start / s;
+        let p1 = end / s;
+        let d = p1 - p0;
+        let current = (p0.x.floor() as i32, p0.y.floor() as i32);
+        let axis = |d: f32, p: f32, cell: i32| {
+            if d > 0. {
+                (1, (cell as f32 + 1. - p) / d, 1. / d)
+            } else if d < 0. {
+                (-1, (p - cell as f32) / -d, -1. / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, t_max_x, t_delta_x) = axis(d.x, p0.x, current.0);
+        let (step_y, t_max_y, t_delta_y) = axis(d.y, p0.y, current.1);
+        Self {
+            current,
+            end: (p1.x.floor() as i32, p1.y.floor() as i32),
+            step: (step_x, step_y),
+            t_max: Vec2::new(t_max_x, t_max_y),
+            t_delta: Vec2::new(t_delta_x, t_delta_y),
+            done: false,
+        }
+    }
+}
+
+impl Iterator for SegmentKeyIter {
+    type Item = Key;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let key = self.current;
+        let t = self.t_max.min_element();
+        // 終点のcellか,次の境界が線分の外
+        if key == self.end || t > 1. {
+            self.done = true;
+        } else if self.t_max.x < self.t_max.y {
+            self.current.0 += self.step.0;
+            self.t_max.x += self.t_delta.x;
+        } else {
+            self.current.1 += self.step.1;
+            self.t_max.y += self.t_delta.y;
+        }
+        Some(key)
+    }
+}
+
 struct KeyIter {
     width: i32,
     start: Key,
@@ -104,13 +280,24 @@ struct KeyIter {
     count: i32,
 }
 
+fn range_contains((min, max): KeyRange, key: Key) -> bool {
+    min.0 <= key.0 && key.0 < max.0 && min.1 <= key.1 && key.1 < max.1
+}
+
 impl KeyIter {
     fn new<const TILE_SIZE: usize>(aabb: impl Into<Aabb>) -> Self {
-        let Aabb { min, max } = aabb.into();
+        Self::from_range(Self::range::<TILE_SIZE>(aabb.into()))
+    }
+
+    fn range<const TILE_SIZE: usize>(Aabb { min, max }: Aabb) -> KeyRange {
         // convert to key space
         let s = TILE_SIZE as f32;
         let min = ((min.x / s).floor() as i32, (min.y / s).floor() as i32);
         let max = ((max.x / s).ceil() as i32, (max.y / s).ceil() as i32);
+        (min, max)
+    }
+
+    fn from_range((min, max): KeyRange) -> Self {
         let width = max.0 - min.0;
         let height = max.1 - min.1;
         let count = width * height;
@@ -294,6 +481,137 @@ mod tests {
         assert_eq!(matches[0], e1);
     }
 
+    #[test]
+    fn query_into_dedups() {
+        let e1 = Entity::from_raw(1);
+        let e2 = Entity::from_raw(2);
+        let mut db = SparseGrid2d::<TILE_SIZE>::default();
+        // 4 cells each
+        db.insert_aabb(Aabb::from_circle(Vec2::ZERO, 0.5), e2);
+        db.insert_aabb(Aabb::from_circle(Vec2::ZERO, 0.5), e1);
+
+        let mut out = vec![Entity::from_raw(99)];
+        db.query_aabb_into(Aabb::from_circle(Vec2::ZERO, 0.5), &mut out);
+        assert_eq!(out, vec![e1, e2]);
+
+        db.query_aabb_into(Aabb::from_circle(vec2(5., 5.), 0.1), &mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn pairs_once() {
+        let e1 = Entity::from_raw(1);
+        let e2 = Entity::from_raw(2);
+        let e3 = Entity::from_raw(3);
+        let mut db = SparseGrid2d::<TILE_SIZE>::default();
+        // e1,e2 share 4 cells, e3 shares 1 cell with both, and is inserted twice
+        db.insert_aabb(Aabb::from_circle(Vec2::ZERO, 0.5), e2);
+        db.insert_aabb(Aabb::from_circle(Vec2::ZERO, 0.5), e1);
+        db.insert_aabb(Aabb::from_circle(vec2(-0.6, -0.6), 0.2), e3);
+        db.insert_aabb(Aabb::from_circle(vec2(-0.6, -0.6), 0.2), e3);
+
+        let mut out = Vec::new();
+        db.pairs_into(&mut out);
+        assert_eq!(out, vec![(e1, e2), (e1, e3), (e2, e3)]);
+
+        db.soft_clear();
+        db.pairs_into(&mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn segment_keys() {
+        let keys: Vec<Key> =
+            SegmentKeyIter::new::<TILE_SIZE>(vec2(0.5, 0.5), vec2(3.5, 0.5)).collect();
+        assert_eq!(keys, vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+        // 負の向き,斜め
+        let keys: Vec<Key> =
+            SegmentKeyIter::new::<TILE_SIZE>(vec2(0.5, 0.2), vec2(-1.5, -1.2)).collect();
+        assert_eq!(keys.first(), Some(&(0, 0)));
+        assert_eq!(keys.last(), Some(&(-2, -2)));
+        // 隣り合うcellだけ進む
+        assert!(keys
+            .windows(2)
+            .all(|w| (w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs() == 1));
+
+        let keys: Vec<Key> =
+            SegmentKeyIter::new::<TILE_SIZE>(vec2(0.5, 0.5), vec2(0.6, 0.7)).collect();
+        assert_eq!(keys, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn ray_in_cell_order() {
+        let mut db = SparseGrid2d::<10>::default();
+        let near = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+        let off = Entity::from_raw(3);
+        db.insert_point(vec2(45., 5.), far);
+        db.insert_point(vec2(15., 5.), near);
+        db.insert_point(vec2(15., 25.), off);
+
+        let hits: Vec<Entity> = db.ray_iter(vec2(1., 5.), vec2(1., 0.), 100.).collect();
+        assert_eq!(hits, vec![near, far]);
+        let hits: Vec<Entity> = db.ray_iter(vec2(1., 5.), vec2(1., 0.), 20.).collect();
+        assert_eq!(hits, vec![near]);
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let e1 = Entity::from_raw(1);
+        let e2 = Entity::from_raw(2);
+        let mut db = SparseGrid2d::<10>::default();
+        let a = Aabb::from_circle(vec2(5., 5.), 2.);
+        let b = Aabb::from_circle(vec2(25., 5.), 2.);
+        db.insert_aabb(a, e1);
+        db.insert_aabb(a, e2);
+
+        // 同じcell内の移動は何もしない
+        db.update(e1, a, Aabb::from_circle(vec2(6., 4.), 2.));
+        assert_eq!(db.query_aabb(a).len(), 2);
+
+        db.update(e1, a, b);
+        let matches = db.query_aabb(a);
+        assert!(!matches.contains(&e1));
+        assert!(matches.contains(&e2));
+        assert!(db.query_aabb(b).contains(&e1));
+
+        db.remove(e2);
+        db.remove(e2);
+        assert!(db.query_aabb(a).is_empty());
+        // 空になったcellは残らない
+        assert_eq!(db.map.len(), 1);
+        db.remove(e1);
+        assert!(db.map.is_empty());
+    }
+
+    // 呼び出し側のold_aabbが入れた時と違っても,入っているcellから動かす
+    #[test]
+    fn update_uses_index() {
+        let e1 = Entity::from_raw(1);
+        let mut db = SparseGrid2d::<10>::default();
+        let a = Aabb::from_circle(vec2(5., 5.), 2.);
+        let b = Aabb::from_circle(vec2(25., 5.), 2.);
+        let c = Aabb::from_circle(vec2(45., 5.), 2.);
+        db.insert_aabb(a, e1);
+
+        db.update(e1, b, c);
+        assert!(db.query_aabb(a).is_empty());
+        assert!(db.query_aabb(c).contains(&e1));
+        assert_eq!(db.map.len(), 1);
+
+        // old_aabbが新しい方と同じcellでも,入っていなければ動かす
+        db.update(e1, a, a);
+        assert!(db.query_aabb(c).is_empty());
+        assert!(db.query_aabb(a).contains(&e1));
+
+        // 入っていなければinsert
+        db.remove(e1);
+        db.update(e1, b, b);
+        assert_eq!(db.query_aabb(b).into_iter().collect::<Vec<_>>(), vec![e1]);
+        assert_eq!(db.map.len(), 1);
+    }
+
     #[test]
     fn query_points_tilesize_10() {
         let mut db = SparseGrid2d::<10>::default();
@@ -0,0 +1,267 @@
+use crate::{
+    data_asset::{DataAsset, DataAssetError, DataAssetErrors, DataAssetPlugin},
+    enemy::EnemyArchetypes,
+};
+use bevy::{prelude::*, sprite::Anchor, utils::HashMap};
+
+const ATLAS_PATH: &str = "sprites_000.atlas.ron";
+// 敵のspriteの名前のerror,atlasを読んだ時のerrorと分ける
+const SPRITE_NAMES_ERROR_KEY: &str = "sprites_000.atlas.ron#enemy sprites";
+const DEFAULT_SPRITE_SIZE: f32 = 8.; //名前が無い時の大きさ
+
+// spriteの切り出し方,名前ごとに矩形とpivot
+#[derive(Asset, TypePath, Debug, Clone, serde::Deserialize)]
+pub struct SpriteAtlasDesc {
+    pub texture: String,  //このファイルのfolderから
+    pub size: (u32, u32), //textureの大きさ
+    pub sprites: Vec<SpriteRegion>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SpriteRegion {
+    pub name: String,
+    pub rect: (u32, u32, u32, u32), //x,y,幅,高さ.左上からのpixel
+    #[serde(default)]
+    pub pivot: (f32, f32), //中心が(0,0),左下が(-0.5,-0.5)
+}
+
+impl DataAsset for SpriteAtlasDesc {
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.sprites.is_empty() {
+            errors.push("sprites is empty".into());
+        }
+        for (i, s) in self.sprites.iter().enumerate() {
+            let mut error = |message: &str| errors.push(format!("{} ({i}): {message}", s.name));
+            if s.name.is_empty() {
+                error("name is empty");
+            } else if self.sprites[..i].iter().any(|t| t.name == s.name) {
+                error("name is used twice");
+            }
+            let (x, y, w, h) = s.rect;
+            if w == 0 || h == 0 {
+                error("rect is empty");
+            }
+            // 足して溢れるのも外
+            if x.checked_add(w).is_none_or(|r| r > self.size.0)
+                || y.checked_add(h).is_none_or(|b| b > self.size.1)
+            {
+                error("rect is out of the texture");
+            }
+            if s.pivot.0.abs() > 0.5 || s.pivot.1.abs() > 0.5 {
+                error("pivot is out of the rect");
+            }
+        }
+    }
+}
+
+impl SpriteAtlasDesc {
+    // texture atlasのlayoutと,名前からの引き
+    fn layout(&self) -> (TextureAtlasLayout, HashMap<String, AtlasSprite>) {
+        let mut layout =
+            TextureAtlasLayout::new_empty(Vec2::new(self.size.0 as f32, self.size.1 as f32));
+        let sprites = self
+            .sprites
+            .iter()
+            .map(|s| {
+                let (x, y, w, h) = s.rect;
+                let min = Vec2::new(x as f32, y as f32);
+                let size = Vec2::new(w as f32, h as f32);
+                let sprite = AtlasSprite {
+                    index: layout.add_texture(Rect::from_corners(min, min + size)),
+                    size,
+                    pivot: Vec2::new(s.pivot.0, s.pivot.1),
+                };
+                (s.name.clone(), sprite)
+            })
+            .collect();
+        (layout, sprites)
+    }
+}
+
+// atlasの中の1つのsprite
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasSprite {
+    pub index: usize,
+    pub size: Vec2, //pixel
+    pub pivot: Vec2,
+}
+impl Default for AtlasSprite {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            size: Vec2::splat(DEFAULT_SPRITE_SIZE),
+            pivot: Vec2::ZERO,
+        }
+    }
+}
+
+// 読み込んだatlas,読めるまでは空
+#[derive(Resource, Default)]
+pub struct SpriteAtlas {
+    texture: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+    sprites: HashMap<String, AtlasSprite>,
+}
+impl SpriteAtlas {
+    pub fn get(&self, name: &str) -> Option<&AtlasSprite> {
+        self.sprites.get(name)
+    }
+    // 名前のsprite,知らない名前なら最初の矩形.scale倍で描く
+    pub fn bundle(&self, name: &str, scale: f32, transform: Transform) -> SpriteSheetBundle {
+        let sprite = self.get(name).copied().unwrap_or_default();
+        SpriteSheetBundle {
+            sprite: Sprite {
+                custom_size: Some(sprite.size * scale),
+                anchor: Anchor::Custom(sprite.pivot),
+                ..default()
+            },
+            atlas: TextureAtlas {
+                layout: self.layout.clone(),
+                index: sprite.index,
+            },
+            texture: self.texture.clone(),
+            transform,
+            ..default()
+        }
+    }
+}
+
+// atlasを読んで,更新されたら作り直す.出ている敵はそのまま
+pub struct SpriteAtlasPlugin;
+impl Plugin for SpriteAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DataAssetPlugin::<SpriteAtlasDesc>::new(&[
+            "atlas.ron",
+            "atlas.json",
+            "atlas.toml",
+        ]))
+        .init_resource::<SpriteAtlas>()
+        .add_systems(PreStartup, load_sprite_atlas_system)
+        .add_systems(
+            Update,
+            (apply_sprite_atlas_system, check_sprite_names_system).chain(),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct SpriteAtlasHandle(Handle<SpriteAtlasDesc>);
+
+fn load_sprite_atlas_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SpriteAtlasHandle(asset_server.load(ATLAS_PATH)));
+}
+
+fn apply_sprite_atlas_system(
+    asset_server: Res<AssetServer>,
+    handle: Res<SpriteAtlasHandle>,
+    mut events: EventReader<AssetEvent<SpriteAtlasDesc>>,
+    descs: Res<Assets<SpriteAtlasDesc>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut atlas: ResMut<SpriteAtlas>,
+) {
+    let mut changed = false;
+    for event in events.read() {
+        changed |= matches!(event, AssetEvent::Added { id } | AssetEvent::Modified { id }
+            if *id == handle.0.id());
+    }
+    if !changed {
+        return;
+    }
+    let (Some(desc), Some(path)) = (descs.get(&handle.0), asset_server.get_path(&handle.0)) else {
+        return;
+    };
+    let texture = match path.resolve_embed(&desc.texture) {
+        Ok(p) => asset_server.load(p),
+        Err(e) => {
+            warn!("{path}: {e}");
+            return;
+        }
+    };
+    let (layout, sprites) = desc.layout();
+    info!("sprite atlas loaded:{}", sprites.len());
+    *atlas = SpriteAtlas {
+        texture,
+        layout: layouts.add(layout),
+        sprites,
+    };
+}
+
+// 敵のspriteの名前がatlasにあるか,無ければerror panelに出す
+fn check_sprite_names_system(
+    atlas: Res<SpriteAtlas>,
+    archetypes: Res<EnemyArchetypes>,
+    mut errors: ResMut<DataAssetErrors>,
+) {
+    if !(atlas.is_changed() || archetypes.is_changed()) || atlas.sprites.is_empty() {
+        return;
+    }
+    let unknown = archetypes
+        .list
+        .iter()
+        .filter(|a| atlas.get(&a.sprite).is_none())
+        .map(|a| DataAssetError {
+            path: ATLAS_PATH.into(),
+            position: None,
+            message: format!("{}: unknown sprite {}", a.name, a.sprite),
+        })
+        .collect();
+    errors.set(SPRITE_NAMES_ERROR_KEY, unknown);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn load() -> SpriteAtlasDesc {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join(ATLAS_PATH);
+        ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn atlas_has_enemy_sprites() {
+        let desc = load();
+        let mut errors = Vec::new();
+        desc.validate(&mut errors);
+        assert!(errors.is_empty(), "{errors:?}");
+        let (layout, sprites) = desc.layout();
+        assert_eq!(layout.len(), desc.sprites.len());
+        for a in EnemyArchetypes::default().list {
+            assert!(sprites.contains_key(&a.sprite), "{}", a.sprite);
+        }
+    }
+
+    #[test]
+    fn regions_and_pivots() {
+        let desc: SpriteAtlasDesc = ron::from_str(
+            r#"(texture: "a.png", size: (32, 16), sprites: [
+                (name: "a", rect: (0, 0, 8, 8)),
+                (name: "wide", rect: (8, 0, 24, 16), pivot: (0., -0.5)),
+            ])"#,
+        )
+        .unwrap();
+        let (layout, sprites) = desc.layout();
+        let wide = sprites["wide"];
+        assert_eq!(wide.size, Vec2::new(24., 16.));
+        assert_eq!(wide.pivot, Vec2::new(0., -0.5));
+        assert_eq!(layout.textures[wide.index], Rect::new(8., 0., 32., 16.));
+        assert_eq!(sprites["a"].index, 0);
+
+        let mut bad = desc.clone();
+        bad.sprites[1].rect.2 = 25;
+        bad.sprites[1].pivot.0 = 1.;
+        bad.sprites[0].name = "wide".into();
+        let mut errors = Vec::new();
+        bad.validate(&mut errors);
+        assert_eq!(errors.len(), 3, "{errors:?}");
+
+        // x+幅がu32を溢れる
+        let mut bad = desc.clone();
+        bad.sprites[1].rect.2 = u32::MAX;
+        let mut errors = Vec::new();
+        bad.validate(&mut errors);
+        assert_eq!(errors.len(), 1, "{errors:?}");
+    }
+}
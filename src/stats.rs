@@ -0,0 +1,65 @@
+use crate::{wave::GameSequence, AppState};
+use bevy::prelude::*;
+
+// 1wave分の集計
+#[derive(Debug, Clone, Default)]
+pub struct WaveStats {
+    pub wave_no: u32,
+    pub kills: u32,
+    pub damage_taken: f32,
+    pub time_alive: f32, //wave内で生きていた時間(sec)
+}
+
+// 1run分の集計,balance調整用
+#[derive(Resource, Debug, Default)]
+pub struct RunStats {
+    pub waves: Vec<WaveStats>,
+}
+impl RunStats {
+    fn current(&mut self) -> Option<&mut WaveStats> {
+        self.waves.last_mut()
+    }
+    pub fn add_kill(&mut self) {
+        if let Some(wave) = self.current() {
+            wave.kills += 1;
+        }
+    }
+    pub fn add_damage(&mut self, damage: f32) {
+        if let Some(wave) = self.current() {
+            wave.damage_taken += damage;
+        }
+    }
+}
+
+pub struct StatsPlugin;
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(OnExit(AppState::Title), reset_run_stats_system)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                begin_wave_stats_system.after(crate::wave::setup_in_game_system),
+            )
+            .add_systems(
+                FixedUpdate,
+                wave_time_stats_system.run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+fn reset_run_stats_system(mut stats: ResMut<RunStats>) {
+    *stats = RunStats::default();
+}
+
+fn begin_wave_stats_system(mut stats: ResMut<RunStats>, game_sequence: Res<GameSequence>) {
+    stats.waves.push(WaveStats {
+        wave_no: game_sequence.wave_no,
+        ..default()
+    });
+}
+
+fn wave_time_stats_system(time: Res<Time>, mut stats: ResMut<RunStats>) {
+    if let Some(wave) = stats.current() {
+        wave.time_alive += time.delta_seconds();
+    }
+}
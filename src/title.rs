@@ -1,6 +1,10 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, window::ReceivedCharacter};
 
-use crate::AppState;
+use crate::{
+    game_rng::RunSeed,
+    inputmng::{InputMngBtn, MENU_CONFIRM},
+    AppState,
+};
 
 const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
@@ -11,13 +15,24 @@ pub struct UITitleData {
     button_entity: Entity,
 }
 
-pub fn setup_title(mut commands: Commands) {
+#[derive(Component)]
+pub struct SeedText;
+
+fn seed_text(run_seed: &RunSeed) -> String {
+    match run_seed.0 {
+        Some(seed) => format!("SEED: {seed}"),
+        None => "SEED: random (type digits)".into(),
+    }
+}
+
+pub fn setup_title(mut commands: Commands, run_seed: Res<RunSeed>) {
     let button_entity = commands
         .spawn(NodeBundle {
             style: Style {
                 // center button
                 width: Val::Percent(100.),
                 height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..default()
@@ -49,18 +64,38 @@ pub fn setup_title(mut commands: Commands) {
                         },
                     ));
                 });
+            parent.spawn((
+                TextBundle::from_section(
+                    seed_text(&run_seed),
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::top(Val::Px(16.)),
+                    ..default()
+                }),
+                SeedText,
+            ));
         })
         .id();
     commands.insert_resource(UITitleData { button_entity });
 }
 
 pub fn title_system(
+    input: Res<ButtonInput<InputMngBtn>>,
     mut next_state: ResMut<NextState<AppState>>,
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
+    // ボタンが無くても決定で進む
+    if input.just_pressed(MENU_CONFIRM) {
+        next_state.set(AppState::InGame);
+    }
     for (interaction, mut color) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
@@ -77,6 +112,37 @@ pub fn title_system(
     }
 }
 
+// 数字でseed入力,BackSpaceで1文字消す
+pub fn title_seed_input_system(
+    mut char_events: EventReader<ReceivedCharacter>,
+    kb: Res<ButtonInput<KeyCode>>,
+    mut run_seed: ResMut<RunSeed>,
+    mut query: Query<&mut Text, With<SeedText>>,
+) {
+    let mut seed = run_seed.0;
+    for ev in char_events.read() {
+        for c in ev.char.chars() {
+            if let Some(d) = c.to_digit(10) {
+                let prev = seed.unwrap_or(0);
+                seed = Some(
+                    prev.checked_mul(10)
+                        .and_then(|v| v.checked_add(d as u64))
+                        .unwrap_or(prev),
+                );
+            }
+        }
+    }
+    if kb.just_pressed(KeyCode::Backspace) {
+        seed = seed.map(|v| v / 10).filter(|v| *v > 0);
+    }
+    if seed != run_seed.0 {
+        run_seed.0 = seed;
+        for mut text in &mut query {
+            text.sections[0].value = seed_text(&run_seed);
+        }
+    }
+}
+
 pub fn cleanup_title(mut commands: Commands, menu_data: Res<UITitleData>) {
     commands.entity(menu_data.button_entity).despawn_recursive();
 }
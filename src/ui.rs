@@ -0,0 +1,71 @@
+use crate::{
+    camera, fixed_step, gameover, levelup, shop, title,
+    ui_game::{self, UiGamePlugin},
+    wave, AppState,
+};
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use std::time::Duration;
+
+// menuの遷移,ボタンか決定の入力で進む.uiを作らないのでwindow無しでも使える
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                title::title_system.run_if(in_state(AppState::Title)),
+                levelup::levelup_system.run_if(in_state(AppState::LevelUp)),
+                shop::shop_system.run_if(in_state(AppState::Shop)),
+                gameover::gameover_system.run_if(in_state(AppState::GameOver)),
+            ),
+        );
+    }
+}
+
+// 各画面のmenu,InGameのui,camera
+pub struct UiPlugin;
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MenuPlugin)
+            //Title
+            .add_systems(
+                OnEnter(AppState::Title),
+                (title::setup_title, ui_game::cleanup_ui_game_system),
+            )
+            .add_systems(
+                Update,
+                title::title_seed_input_system.run_if(in_state(AppState::Title)),
+            )
+            .add_systems(OnExit(AppState::Title), title::cleanup_title)
+            //LevelUp
+            .add_systems(OnEnter(AppState::LevelUp), levelup::setup_levelup)
+            .add_systems(
+                OnExit(AppState::LevelUp),
+                (levelup::cleanup_levelup, ui_game::cleanup_ui_game_system),
+            )
+            //Shop
+            .add_systems(OnEnter(AppState::Shop), shop::setup_shop)
+            .add_systems(OnExit(AppState::Shop), shop::cleanup_shop)
+            //GameOver
+            .add_systems(OnEnter(AppState::GameOver), gameover::setup_gameover)
+            .add_systems(OnExit(AppState::GameOver), gameover::cleanup_gameover)
+            //InGame
+            .add_plugins(UiGamePlugin)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (
+                    ui_game::setup_ui_game,              //ui作る
+                    ui_game::update_ui_game_wave_system, //wave表示更新
+                )
+                    .chain()
+                    .after(wave::setup_in_game_system),
+            )
+            .add_systems(
+                PostUpdate,
+                camera::update_camera_system
+                    .after(fixed_step::interpolate_transform_system)
+                    .run_if(on_timer(Duration::from_secs_f32(1. / 60.)))
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
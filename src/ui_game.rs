@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 
-use crate::{AppState, GameFonts, GameSequence, WaveStatus};
+use crate::{
+    wave::{GameSequence, WaveStatus},
+    AppState, GameFonts,
+};
 
 pub struct UiGamePlugin;
 
@@ -85,10 +88,14 @@ pub fn setup_ui_game(mut commands: Commands, font: Res<GameFonts>) {
     commands.insert_resource(UIGameData { button_entity }); //上書きされる
 }
 
-pub fn cleanup_ui_game_system(mut commands: Commands, ui_game_data: Res<UIGameData>) {
-    commands
-        .entity(ui_game_data.button_entity)
-        .despawn_recursive();
+// 起動直後のtitle等,無い時もある
+pub fn cleanup_ui_game_system(mut commands: Commands, ui_game_data: Option<Res<UIGameData>>) {
+    if let Some(ui_game_data) = ui_game_data {
+        commands
+            .entity(ui_game_data.button_entity)
+            .despawn_recursive();
+        commands.remove_resource::<UIGameData>();
+    }
 }
 
 // waveの開始
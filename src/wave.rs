@@ -0,0 +1,118 @@
+use crate::{
+    components::*,
+    enemy::EnemyCount,
+    physics::{PhysicsResource, SHM},
+    pool::{EntityPool, Pooled},
+    AppState, GameConfig,
+};
+use bevy::prelude::*;
+
+// runの開始,終了とwaveの進行
+pub struct WavePlugin;
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameSequence { ..default() })
+            .insert_resource(WaveStatus { ..default() })
+            .add_systems(OnExit(AppState::Title), setup_game_sequence_system)
+            // GameOver,demo終了でtitleに戻った時
+            .add_systems(OnEnter(AppState::Title), cleanup_run_system)
+            .add_systems(OnEnter(AppState::InGame), setup_in_game_system)
+            .add_systems(OnExit(AppState::InGame), cleanup_in_game_system)
+            .add_systems(
+                FixedUpdate,
+                update_wave_system
+                    .in_set(GameSystemSet::PostUpdate)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+// Gameシーケンス
+#[derive(Resource)]
+pub struct GameSequence {
+    started: bool,
+    pub wave_no: u32,
+    pub exp: u32, //拾った経験値
+}
+impl Default for GameSequence {
+    fn default() -> Self {
+        Self {
+            started: false,
+            wave_no: 0,
+            exp: 0,
+        }
+    }
+}
+
+// waveの状態
+#[derive(Resource)]
+pub struct WaveStatus {
+    pub timer: Timer,
+}
+impl Default for WaveStatus {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(60.0, TimerMode::Once),
+        }
+    }
+}
+
+// GameSequence初期化処理
+fn setup_game_sequence_system(mut game_sequence: ResMut<GameSequence>) {
+    // clear
+    *game_sequence = GameSequence { ..default() };
+}
+
+// InGame初期化処理
+pub fn setup_in_game_system(
+    game_config: Query<&GameConfig>,
+    mut game_sequence: ResMut<GameSequence>,
+    mut wave_status: ResMut<WaveStatus>,
+) {
+    // next wave
+    if game_sequence.started {
+        game_sequence.wave_no += 1;
+    } else {
+        game_sequence.started = true;
+    }
+    // clear
+    *wave_status = WaveStatus { ..default() };
+    // for debug,time短い設定
+    if cfg!(debug_assertions) && game_config.get_single().unwrap().dbg_least_time {
+        wave_status.timer = Timer::from_seconds(5.0, TimerMode::Once);
+    }
+}
+
+// InGame終了処理
+fn cleanup_in_game_system() {
+    //
+}
+
+// runの終了処理,gameplayのentityを全部消す
+fn cleanup_run_system(
+    mut commands: Commands,
+    mut enemy_count: ResMut<EnemyCount>,
+    mut pool: ResMut<EntityPool>,
+    mut shm: ResMut<SHM>,
+    physics_resource: Res<PhysicsResource>,
+    query: Query<Entity, Or<(With<PhysicalObj>, With<Pooled>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    enemy_count.count = 0;
+    *pool = EntityPool::default();
+    // InGameの外でdespawnしたのでtickで消せない,broadphaseごと作り直す
+    *shm = SHM::new(physics_resource.broadphase);
+}
+
+fn update_wave_system(
+    time: Res<Time>,
+    mut wave_status: ResMut<WaveStatus>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    wave_status.timer.tick(time.delta());
+    if wave_status.timer.finished() {
+        next_state.set(AppState::LevelUp);
+    }
+}
@@ -0,0 +1,285 @@
+// window無しでAppを組んで,状態遷移,wave,敵の数,弾の当たりを確かめる
+use bevy::prelude::*;
+use bevyruman::{
+    components::*,
+    enemy::{EnemyCount, EnemyWaves},
+    headless::gameplay_app,
+    inputmng::{AimInput, InputMngBtn, MENU_CONFIRM},
+    shape::Shape,
+    stats::RunStats,
+    wave::{GameSequence, WaveStatus},
+    AppState,
+};
+
+// テスト用のgame,1stepで1tick進む
+struct TestGame {
+    app: App,
+}
+
+impl TestGame {
+    fn new() -> Self {
+        Self {
+            app: gameplay_app(1),
+        }
+    }
+
+    // 敵を湧かせない
+    fn without_spawn(mut self) -> Self {
+        self.app.world.resource_mut::<EnemyCount>().max = 0;
+        self
+    }
+
+    fn step(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    fn state(&self) -> AppState {
+        *self.app.world.resource::<State<AppState>>().get()
+    }
+
+    // menuで決定を押して離す,次の画面に進む
+    fn press_menu_button(&mut self) {
+        let next = match self.state() {
+            AppState::Title | AppState::Shop => AppState::InGame,
+            AppState::LevelUp => AppState::Shop,
+            AppState::GameOver => AppState::Title,
+            AppState::InGame => panic!("no menu in game"),
+        };
+        self.press(MENU_CONFIRM);
+        self.step(1);
+        self.release(MENU_CONFIRM);
+        self.step(1);
+        assert_eq!(self.state(), next);
+    }
+
+    fn press(&mut self, btn: InputMngBtn) {
+        self.app
+            .world
+            .resource_mut::<ButtonInput<InputMngBtn>>()
+            .press(btn);
+    }
+
+    fn release(&mut self, btn: InputMngBtn) {
+        self.app
+            .world
+            .resource_mut::<ButtonInput<InputMngBtn>>()
+            .release(btn);
+    }
+
+    fn aim(&mut self, pos: Vec2) {
+        self.app.world.resource_mut::<AimInput>().pos = Some(pos);
+    }
+
+    // waveの残り時間を飛ばす
+    fn finish_wave(&mut self) {
+        let mut wave = self.app.world.resource_mut::<WaveStatus>();
+        let duration = wave.timer.duration();
+        wave.timer.set_elapsed(duration);
+        self.step(2);
+    }
+
+    fn wave_no(&self) -> u32 {
+        self.app.world.resource::<GameSequence>().wave_no
+    }
+
+    fn enemies(&mut self) -> usize {
+        let mut query = self.app.world.query_filtered::<(), With<Enemy>>();
+        query.iter(&self.app.world).count()
+    }
+
+    fn kills(&self) -> u32 {
+        let stats = self.app.world.resource::<RunStats>();
+        stats.waves.last().map_or(0, |w| w.kills)
+    }
+
+    fn player(&mut self) -> Entity {
+        let mut query = self.app.world.query_filtered::<Entity, With<Player>>();
+        query.single(&self.app.world)
+    }
+}
+
+// levelのファイルを読んでから始まる
+#[test]
+fn level_file_is_loaded() {
+    let game = TestGame::new();
+    assert!(!game.app.world.resource::<EnemyWaves>().waves.is_empty());
+}
+
+#[test]
+fn title_ingame_levelup_shop_ingame() {
+    let mut game = TestGame::new().without_spawn();
+    assert_eq!(game.state(), AppState::Title);
+    game.press_menu_button();
+    game.step(10);
+    assert_eq!(game.state(), AppState::InGame);
+    assert_eq!(game.wave_no(), 0);
+
+    game.finish_wave();
+    assert_eq!(game.state(), AppState::LevelUp);
+    game.press_menu_button();
+    assert_eq!(game.state(), AppState::Shop);
+    game.press_menu_button();
+    assert_eq!(game.state(), AppState::InGame);
+    assert_eq!(game.wave_no(), 1);
+    assert_eq!(game.app.world.resource::<RunStats>().waves.len(), 2);
+}
+
+#[test]
+fn new_run_resets_wave_count() {
+    let mut game = TestGame::new().without_spawn();
+    game.press_menu_button();
+    for _ in 0..3 {
+        game.finish_wave();
+        game.press_menu_button();
+        game.press_menu_button();
+    }
+    assert_eq!(game.wave_no(), 3);
+
+    // playerがやられたらGameOver,titleから始め直す
+    game.step(1);
+    let player = game.player();
+    game.app.world.get_mut::<Health>(player).unwrap().hp = 0.;
+    game.step(2);
+    assert_eq!(game.state(), AppState::GameOver);
+    game.press_menu_button();
+    game.press_menu_button();
+    game.step(1);
+    assert_eq!(game.wave_no(), 0);
+    assert_eq!(game.enemies(), 0);
+}
+
+#[test]
+fn enemies_spawn_up_to_max() {
+    let mut game = TestGame::new();
+    game.app.world.resource_mut::<EnemyCount>().max = 50;
+    game.press_menu_button();
+    game.step(30);
+    assert_eq!(game.app.world.resource::<EnemyCount>().count, 50);
+    assert_eq!(game.enemies(), 50);
+}
+
+#[test]
+fn bullet_kills_enemy() {
+    let mut game = TestGame::new().without_spawn();
+    game.press_menu_button();
+    game.step(1);
+    let pos = Vec2::new(40., 0.);
+    let enemy = game
+        .app
+        .world
+        .spawn((
+            Transform::from_translation(pos.extend(0.)),
+            Enemy,
+            PhysicalObj {
+                old_pos: pos,
+                ..default()
+            },
+            CollideCircle::new(CollisionLayer::Enemy),
+            Health::from_max(1.),
+        ))
+        .id();
+    game.app.world.resource_mut::<EnemyCount>().count += 1;
+
+    game.aim(pos);
+    game.press(InputMngBtn::Shot);
+    game.step(60);
+    assert_eq!(game.kills(), 1);
+    assert!(game.app.world.get_entity(enemy).is_none());
+    assert_eq!(game.app.world.resource::<EnemyCount>().count, 0);
+}
+
+// titleで待った時間が違っても同じseedなら同じ敵が湧く
+#[test]
+fn spawn_does_not_depend_on_menu_time() {
+    let positions = |wait: u32| {
+        let mut game = TestGame::new();
+        game.step(wait);
+        game.press_menu_button();
+        game.step(30);
+        let mut query = game.app.world.query_filtered::<&Transform, With<Enemy>>();
+        let mut positions: Vec<_> = query
+            .iter(&game.app.world)
+            .map(|tf| tf.translation.xy().to_array())
+            .collect();
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        positions
+    };
+    let a = positions(0);
+    assert!(!a.is_empty());
+    assert_eq!(a, positions(7));
+}
+
+// 線分の通るcellの外,経路の途中で弾の幅の分だけかすった敵
+#[test]
+fn bullet_hits_off_centerline_mid_path() {
+    let mut game = TestGame::new().without_spawn();
+    game.press_menu_button();
+    game.step(1);
+    let pos = Vec2::new(200., 8.);
+    let enemy = game
+        .app
+        .world
+        .spawn((
+            Transform::from_translation(pos.extend(0.)),
+            Enemy,
+            PhysicalObj {
+                old_pos: pos,
+                ..default()
+            },
+            CollideCircle::new(CollisionLayer::Enemy),
+            Health::from_max(1.),
+        ))
+        .id();
+    game.app.world.resource_mut::<EnemyCount>().count += 1;
+    game.step(1);
+    // y=-1の線分,1tickで150から250まで
+    game.app.world.spawn((
+        Transform::from_translation(Vec3::new(250., -1., 0.)),
+        PhysicalObj {
+            old_pos: Vec2::new(150., -1.),
+            ..default()
+        },
+        HitCircle {
+            radius: 0.,
+            shape: Shape::OrientedBox {
+                half_width: 4.,
+                half_height: 6.,
+            },
+            ..HitCircle::new(CollisionLayer::PlayerShot)
+        },
+        DamageSource { damage: 1. },
+        FromPlayer,
+    ));
+    game.step(2);
+    assert!(game.app.world.get_entity(enemy).is_none());
+    assert_eq!(game.kills(), 1);
+}
+
+// pickupはplayerに当たり判定する層の時だけ拾える
+#[test]
+fn pickup_follows_collision_mask() {
+    let mut game = TestGame::new().without_spawn();
+    game.press_menu_button();
+    game.step(1);
+    let spawn_pickup = |game: &mut TestGame, overlap: CollisionMask| {
+        game.app.world.spawn((
+            Transform::from_xyz(5., 0., 0.),
+            Pickup { exp: 1 },
+            HitCircle {
+                filter: CollisionFilter {
+                    overlap,
+                    ..CollisionFilter::new(CollisionLayer::Pickup)
+                },
+                ..HitCircle::new(CollisionLayer::Pickup)
+            },
+            Lifetime(Timer::from_seconds(30., TimerMode::Once)),
+        ));
+        game.step(1);
+        game.app.world.resource::<GameSequence>().exp
+    };
+    assert_eq!(spawn_pickup(&mut game, CollisionMask::NONE), 0);
+    let player = CollisionMask::of(&[CollisionLayer::Player]);
+    assert_eq!(spawn_pickup(&mut game, player), 1);
+}